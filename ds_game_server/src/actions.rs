@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::ActionsConfig;
+
+/// Actions a client may send with `player`/`action`, anything else is rejected.
+///
+/// The payload is validated by deserializing it: `{"action": "jump"}`, unknown actions
+/// and unexpected fields fail. Variants are structs, even without parameters, so that
+/// `deny_unknown_fields` applies to them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PlayerAction {
+    Jump {},
+    #[serde(rename = "spawn_box50cm")]
+    SpawnBox50cm {},
+}
+
+impl PlayerAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlayerAction::Jump { .. } => "jump",
+            PlayerAction::SpawnBox50cm { .. } => "spawn_box50cm",
+        }
+    }
+
+    /// Actions creating a prop are handled by `dyingstar_props`, which sends the
+    /// resulting `add_prop` itself; the others are simulated by the game server.
+    pub fn creates_prop(&self) -> bool {
+        matches!(self, PlayerAction::SpawnBox50cm { .. })
+    }
+
+    fn default_cooldown(&self) -> Duration {
+        match self {
+            PlayerAction::Jump { .. } => Duration::from_millis(500),
+            PlayerAction::SpawnBox50cm { .. } => Duration::from_millis(2000),
+        }
+    }
+}
+
+/// Checks the actions sent by the players against the configured whitelist and
+/// cooldowns.
+pub struct ActionGate {
+    config: ActionsConfig,
+    /// Last accepted use of each action, keyed by player then action name.
+    last_used: Mutex<HashMap<String, HashMap<&'static str, Instant>>>,
+}
+
+impl ActionGate {
+    pub fn new(config: ActionsConfig) -> Self {
+        Self {
+            config,
+            last_used: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Accepts the action and starts its cooldown, or returns why it is rejected.
+    pub fn check(&self, player_id: &str, action: &PlayerAction) -> Result<(), String> {
+        let name = action.name();
+        if !self.config.enabled.iter().any(|enabled| enabled == name) {
+            return Err(format!("action {} is disabled", name));
+        }
+        let cooldown = self
+            .config
+            .cooldowns_ms
+            .get(name)
+            .map(|ms| Duration::from_millis(*ms))
+            .unwrap_or_else(|| action.default_cooldown());
+
+        let mut last_used = self.last_used.lock().unwrap();
+        let player = last_used.entry(player_id.to_string()).or_default();
+        if let Some(last) = player.get(name) {
+            let elapsed = last.elapsed();
+            if elapsed < cooldown {
+                return Err(format!("action {} on cooldown for {:?}", name, cooldown - elapsed));
+            }
+        }
+        player.insert(name, Instant::now());
+        Ok(())
+    }
+
+    /// Forgets the cooldowns of a player who left.
+    pub fn forget(&self, player_id: &str) {
+        self.last_used.lock().unwrap().remove(player_id);
+    }
+}
@@ -0,0 +1,39 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Label of the MAC computed by the game server, so that a MAC of one side can never
+/// be replayed as the other's.
+pub const GAME_SERVER_LABEL: &str = "ds_game_server";
+/// Label of the MAC computed by Horizon.
+pub const HORIZON_LABEL: &str = "ds_horizon";
+
+/// 32 random bytes, hex encoded, never reused for two handshakes.
+pub fn nonce() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).expect("OS random generator is available");
+    hex::encode(bytes)
+}
+
+fn mac(secret: &str, label: &str, first: &str, second: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    for part in [label, first, second] {
+        mac.update(part.as_bytes());
+        mac.update(b"\n");
+    }
+    mac
+}
+
+/// Hex encoded HMAC-SHA256 of `label`, `first` and `second` with the shared secret.
+pub fn sign(secret: &str, label: &str, first: &str, second: &str) -> String {
+    hex::encode(mac(secret, label, first, second).finalize().into_bytes())
+}
+
+/// Checks a MAC made by `sign`, in constant time.
+pub fn verify(secret: &str, label: &str, first: &str, second: &str, signature: &str) -> bool {
+    match hex::decode(signature) {
+        Ok(bytes) => mac(secret, label, first, second).verify_slice(&bytes).is_ok(),
+        Err(_) => false,
+    }
+}
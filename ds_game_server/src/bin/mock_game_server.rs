@@ -0,0 +1,447 @@
+//! Mock of the Godot game server for local development and integration tests.
+//!
+//! Speaks the protocol `ds_game_server` expects (handshake, hello/welcome, ping,
+//! time sync, acks, handoffs), integrates simple kinematics for the players from their move
+//! inputs and streams `players`/`position` and `props`/`position` on every tick.
+//!
+//! ```bash
+//! cargo run --bin mock_game_server -- --listen 0.0.0.0:8980 --tick-ms 33
+//! ```
+
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use plugin_ds_game_server::auth;
+use plugin_ds_game_server::protocol::{
+    server_time_ms, AckData, AuthResponseData, GameServerMessage, HandoffData, HandoffStateData, NackData, PlayerMoveData,
+    SyncWorldData, TimeRequestData, TimeResponseData, WelcomeData, WireFormat,
+};
+use plugin_ds_game_server::regions::position_of;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::MissedTickBehavior;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use tracing::{debug, error, info, warn};
+
+/// Speed of a player moving with a unit input direction, in meters per second.
+const PLAYER_SPEED: f64 = 5.0;
+/// Vertical speed given by a jump.
+const JUMP_SPEED: f64 = 5.0;
+const GRAVITY: f64 = 9.81;
+
+type WsSink = SplitSink<WebSocketStream<TcpStream>, Message>;
+type WsStream = SplitStream<WebSocketStream<TcpStream>>;
+
+struct Options {
+    listen: String,
+    tick: Duration,
+    /// Same as `shared_secret` in `ds_game_server.toml`, empty skips the handshake.
+    secret: String,
+    /// Behave like game servers that do not answer `request_id` with acks.
+    no_acks: bool,
+}
+
+impl Options {
+    fn from_args() -> Result<Self, String> {
+        let mut options = Options {
+            listen: std::env::var("MOCK_GAME_SERVER_LISTEN").unwrap_or_else(|_| "0.0.0.0:8980".to_string()),
+            tick: Duration::from_millis(33),
+            secret: std::env::var("DS_GAME_SERVER_SHARED_SECRET").unwrap_or_default(),
+            no_acks: false,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--listen" => options.listen = args.next().ok_or("--listen needs an address")?,
+                "--tick-ms" => {
+                    let ms: u64 = args.next().and_then(|ms| ms.parse().ok()).ok_or("--tick-ms needs milliseconds")?;
+                    options.tick = Duration::from_millis(ms.max(1));
+                }
+                "--secret" => options.secret = args.next().ok_or("--secret needs a value")?,
+                "--no-acks" => options.no_acks = true,
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// A simulated player or prop, `state` is the JSON received from Horizon with its
+/// `position` kept up to date.
+struct Body {
+    state: Value,
+    /// Latest move input direction.
+    input: [f64; 3],
+    /// `seq` of the latest move applied, echoed as `last_seq`.
+    last_seq: Option<u64>,
+    /// Vertical speed of a jump in progress.
+    vertical: f64,
+    /// Height the body lands back on after a jump.
+    ground: f64,
+    /// Leaving for another instance, no longer simulated until the commit or abort.
+    frozen: bool,
+    /// Joining from another instance, not reported until the commit.
+    hidden: bool,
+}
+
+impl Body {
+    fn new(state: Value) -> Self {
+        let ground = position_of(&state).map(|p| p[1]).unwrap_or_default();
+        Self {
+            state,
+            input: [0.0; 3],
+            last_seq: None,
+            vertical: 0.0,
+            ground,
+            frozen: false,
+            hidden: false,
+        }
+    }
+
+    fn velocity(&self) -> [f64; 3] {
+        let [x, y, z] = self.input;
+        [x * PLAYER_SPEED, y * PLAYER_SPEED + self.vertical, z * PLAYER_SPEED]
+    }
+
+    fn step(&mut self, dt: f64) {
+        if self.frozen {
+            return;
+        }
+        let Some(mut position) = position_of(&self.state) else {
+            return;
+        };
+        let velocity = self.velocity();
+        for (axis, speed) in velocity.iter().enumerate() {
+            position[axis] += speed * dt;
+        }
+        self.ground += self.input[1] * PLAYER_SPEED * dt;
+        if self.vertical != 0.0 {
+            self.vertical -= GRAVITY * dt;
+            if position[1] <= self.ground {
+                position[1] = self.ground;
+                self.vertical = 0.0;
+            }
+        }
+        self.state["position"] = vec3(position);
+    }
+
+    /// State sent back to Horizon, with the current velocity and the last applied input.
+    fn report(&self) -> Value {
+        let mut state = self.state.clone();
+        state["velocity"] = vec3(self.velocity());
+        if let Some(seq) = self.last_seq {
+            state["last_seq"] = seq.into();
+        }
+        state
+    }
+}
+
+fn vec3([x, y, z]: [f64; 3]) -> Value {
+    json!({ "x": x, "y": y, "z": z })
+}
+
+fn vec3_of(value: &Value) -> Option<[f64; 3]> {
+    Some([value["x"].as_f64()?, value["y"].as_f64()?, value["z"].as_f64()?])
+}
+
+#[derive(Default)]
+struct World {
+    planets: Vec<Value>,
+    /// Players by props `uuid`.
+    players: HashMap<String, Body>,
+    /// Props by `uuid`.
+    props: HashMap<String, Body>,
+}
+
+impl World {
+    fn add_player(&mut self, player: Value) -> Result<(), String> {
+        let uuid = player["uuid"].as_str().ok_or("player without uuid")?.to_string();
+        self.players.insert(uuid, Body::new(player));
+        Ok(())
+    }
+
+    fn add_prop(&mut self, prop: Value) -> Result<(), String> {
+        let uuid = prop["uuid"].as_str().ok_or("prop without uuid")?.to_string();
+        self.props.insert(uuid, Body::new(prop));
+        Ok(())
+    }
+
+    /// Player of a move, which is keyed by Horizon `PlayerId` (`internal_uuid`).
+    fn player_by_internal_id(&mut self, player_id: &str) -> Option<&mut Body> {
+        self.players.values_mut().find(|body| body.state["internal_uuid"] == player_id)
+    }
+
+    fn apply_move(&mut self, player_id: &str, data: &Value) {
+        let Some(body) = self.player_by_internal_id(player_id) else {
+            debug!("move of unknown player {}", player_id);
+            return;
+        };
+        if let Some(dir) = vec3_of(&data["dir"]) {
+            body.input = dir;
+        }
+        if let Some(seq) = data["seq"].as_u64() {
+            body.last_seq = Some(seq);
+        }
+        if !data["rot"].is_null() {
+            body.state["rotation"] = data["rot"].clone();
+        }
+    }
+
+    fn step(&mut self, dt: f64) {
+        for body in self.players.values_mut().chain(self.props.values_mut()) {
+            body.step(dt);
+        }
+    }
+
+    fn positions(&self) -> (Value, Value) {
+        let players = self.players.values().filter(|b| !b.hidden).map(Body::report).collect();
+        let props = self.props.values().map(Body::report).collect();
+        (Value::Array(players), Value::Array(props))
+    }
+}
+
+/// Answer to a command carrying a `request_id`.
+fn answer(options: &Options, request_id: Option<String>, result: Result<(), String>) -> Option<GameServerMessage> {
+    let request_id = request_id.filter(|_| !options.no_acks)?;
+    Some(match result {
+        Ok(()) => GameServerMessage::Ack(AckData { request_id }),
+        Err(reason) => GameServerMessage::Nack(NackData { request_id, reason }),
+    })
+}
+
+/// Applies a message from Horizon to the world and returns the reply, if any.
+fn handle(world: &Mutex<World>, options: &Options, format: &mut WireFormat, message: GameServerMessage) -> Option<GameServerMessage> {
+    let mut world = world.lock().unwrap();
+    match message {
+        GameServerMessage::Hello(hello) => {
+            let encoding = hello.encodings.first().copied().unwrap_or(WireFormat::Json);
+            info!("Horizon said hello, using {:?}", encoding);
+            *format = encoding;
+            Some(GameServerMessage::Welcome(WelcomeData { encoding }))
+        }
+        GameServerMessage::Ping(ping) => Some(GameServerMessage::Pong(ping)),
+        GameServerMessage::TimeResponse(TimeResponseData { t0, t1, t2 }) => {
+            let t3 = server_time_ms() as i64;
+            let (t0, t1, t2) = (t0 as i64, t1 as i64, t2 as i64);
+            info!("Horizon clock offset {} ms, round trip {} ms", ((t1 - t0) + (t2 - t3)) / 2, (t3 - t0) - (t2 - t1));
+            None
+        }
+        GameServerMessage::AddProps(data) => {
+            world.planets.extend(data.planets);
+            let result = world.add_player(data.player);
+            answer(options, data.request_id, result)
+        }
+        GameServerMessage::AddProp(data) => {
+            let result = world.add_prop(data.box50cm);
+            answer(options, data.request_id, result)
+        }
+        GameServerMessage::SyncWorld(SyncWorldData { planets, players, boxes50cm }) => {
+            info!("World resync: {} planets, {} players, {} props", planets.len(), players.len(), boxes50cm.len());
+            *world = World { planets, ..World::default() };
+            for player in players {
+                let _ = world.add_player(player);
+            }
+            for prop in boxes50cm {
+                let _ = world.add_prop(prop);
+            }
+            None
+        }
+        GameServerMessage::PlayerMove { player_id, data } => {
+            world.apply_move(&player_id, &data);
+            None
+        }
+        GameServerMessage::PlayersMove(moves) => {
+            for PlayerMoveData { player_id, data } in moves {
+                world.apply_move(&player_id, &data);
+            }
+            None
+        }
+        GameServerMessage::PlayerRemove(data) => {
+            world.players.remove(&data.player_uuid);
+            None
+        }
+        GameServerMessage::PlayerAction(data) => {
+            if let Some(body) = world.players.get_mut(&data.player_uuid) {
+                if data.action.name() == "jump" && body.vertical == 0.0 {
+                    body.vertical = JUMP_SPEED;
+                }
+            }
+            None
+        }
+        GameServerMessage::HandoffFreeze(HandoffData { handoff_id, player_uuid }) => match world.players.get_mut(&player_uuid) {
+            Some(body) => {
+                body.frozen = true;
+                Some(GameServerMessage::HandoffState(HandoffStateData { handoff_id, player: body.report() }))
+            }
+            None => {
+                warn!("Handoff {} of unknown player {}", handoff_id, player_uuid);
+                None
+            }
+        },
+        GameServerMessage::HandoffPrepare(HandoffStateData { handoff_id, player }) => {
+            let player_uuid = player["uuid"].as_str().unwrap_or_default().to_string();
+            let mut body = Body::new(player.clone());
+            body.hidden = true;
+            // keep moving the way it did on the other instance
+            if let Some(velocity) = vec3_of(&player["velocity"]) {
+                body.input = velocity.map(|speed| speed / PLAYER_SPEED);
+            }
+            world.players.insert(player_uuid.clone(), body);
+            Some(GameServerMessage::HandoffReady(HandoffData { handoff_id, player_uuid }))
+        }
+        GameServerMessage::HandoffCommit(data) => {
+            if world.players.get(&data.player_uuid).is_some_and(|body| body.frozen) {
+                world.players.remove(&data.player_uuid);
+            } else if let Some(body) = world.players.get_mut(&data.player_uuid) {
+                body.hidden = false;
+            }
+            None
+        }
+        GameServerMessage::HandoffAbort(data) => {
+            if world.players.get(&data.player_uuid).is_some_and(|body| body.hidden) {
+                world.players.remove(&data.player_uuid);
+            } else if let Some(body) = world.players.get_mut(&data.player_uuid) {
+                body.frozen = false;
+            }
+            None
+        }
+        other => {
+            warn!("Ignoring {}/{}", other.namespace(), other.event());
+            None
+        }
+    }
+}
+
+async fn send(sink: &mut WsSink, format: WireFormat, message: &GameServerMessage) -> Result<(), String> {
+    let frame = if message.is_high_frequency() && format == WireFormat::MessagePack {
+        Message::binary(message.to_msgpack())
+    } else {
+        Message::text(message.to_json())
+    };
+    sink.send(frame).await.map_err(|e| e.to_string())
+}
+
+async fn receive(stream: &mut WsStream) -> Result<Option<GameServerMessage>, String> {
+    loop {
+        let frame = match stream.next().await {
+            Some(frame) => frame.map_err(|e| e.to_string())?,
+            None => return Ok(None),
+        };
+        let message = match frame {
+            Message::Text(text) => GameServerMessage::from_json(text.as_str()),
+            Message::Binary(bytes) => GameServerMessage::from_binary(&bytes),
+            Message::Close(_) => return Ok(None),
+            _ => continue,
+        };
+        match message {
+            Ok(message) => return Ok(Some(message)),
+            Err(e) => warn!("Rejected message: {}", e),
+        }
+    }
+}
+
+/// Game server side of the shared-secret handshake.
+async fn authenticate(sink: &mut WsSink, stream: &mut WsStream, secret: &str) -> Result<(), String> {
+    let Some(GameServerMessage::AuthChallenge(challenge)) = receive(stream).await? else {
+        return Err("expected server/auth_challenge".to_string());
+    };
+    let nonce = auth::nonce();
+    let mac = auth::sign(secret, auth::GAME_SERVER_LABEL, &challenge.nonce, &nonce);
+    send(sink, WireFormat::Json, &GameServerMessage::AuthResponse(AuthResponseData { nonce: nonce.clone(), mac })).await?;
+    match receive(stream).await? {
+        Some(GameServerMessage::AuthProof(proof)) if auth::verify(secret, auth::HORIZON_LABEL, &nonce, &challenge.nonce, &proof.mac) => Ok(()),
+        _ => Err("Horizon failed authentication".to_string()),
+    }
+}
+
+async fn serve(tcp: TcpStream, world: Arc<Mutex<World>>, options: Arc<Options>) -> Result<(), String> {
+    let ws = tokio_tungstenite::accept_async(tcp).await.map_err(|e| e.to_string())?;
+    let (mut sink, mut stream) = ws.split();
+    if !options.secret.is_empty() {
+        authenticate(&mut sink, &mut stream, &options.secret).await?;
+        info!("Horizon authenticated");
+    }
+
+    // a real game server would repeat it to follow the drift
+    send(&mut sink, WireFormat::Json, &GameServerMessage::TimeRequest(TimeRequestData { t0: server_time_ms() })).await?;
+
+    let mut format = WireFormat::Json;
+    let mut ticker = tokio::time::interval(options.tick);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        tokio::select! {
+            message = receive(&mut stream) => {
+                let Some(message) = message? else {
+                    return Ok(());
+                };
+                debug!("received {}/{}", message.namespace(), message.event());
+                if let Some(reply) = handle(&world, &options, &mut format, message) {
+                    send(&mut sink, format, &reply).await?;
+                }
+            }
+            _ = ticker.tick() => {
+                let (players, props) = world.lock().unwrap().positions();
+                send(&mut sink, format, &GameServerMessage::PlayersPosition(players)).await?;
+                send(&mut sink, format, &GameServerMessage::PropsPosition(props)).await?;
+            }
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .init();
+
+    let options = match Options::from_args() {
+        Ok(options) => Arc::new(options),
+        Err(e) => {
+            eprintln!("{}\nusage: mock_game_server [--listen ADDR] [--tick-ms MS] [--secret SECRET] [--no-acks]", e);
+            std::process::exit(2);
+        }
+    };
+    let listener = match TcpListener::bind(&options.listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Cannot listen on {}: {}", options.listen, e);
+            std::process::exit(1);
+        }
+    };
+    info!("Mock game server listening on {} (tick {:?})", options.listen, options.tick);
+
+    // one world for every connection, it survives Horizon reconnects like the real one
+    let world = Arc::new(Mutex::new(World::default()));
+    let simulation = Arc::clone(&world);
+    let tick = options.tick;
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(tick);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            simulation.lock().unwrap().step(tick.as_secs_f64());
+        }
+    });
+
+    loop {
+        let (tcp, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Accept failed: {}", e);
+                continue;
+            }
+        };
+        info!("Horizon connected from {}", peer);
+        let world = Arc::clone(&world);
+        let options = Arc::clone(&options);
+        tokio::spawn(async move {
+            match serve(tcp, world, options).await {
+                Ok(()) => info!("Horizon {} disconnected", peer),
+                Err(e) => warn!("Connection with {} failed: {}", peer, e),
+            }
+        });
+    }
+}
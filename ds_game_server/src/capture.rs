@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::sync::mpsc;
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, warn};
+
+use crate::protocol::{GameServerMessage, ProtocolError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Received from the game server.
+    In,
+    /// Sent to the game server.
+    Out,
+}
+
+/// One line of a capture file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    /// Milliseconds since the recording started, used to replay with the same timing.
+    pub t_ms: u64,
+    /// Wall clock time, to match the record with the logs.
+    pub at: String,
+    /// Name of the region whose link carried the frame.
+    pub region: String,
+    pub direction: Direction,
+    /// A text frame as it was on the wire, even if it was malformed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// A binary frame, hex encoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary: Option<String>,
+}
+
+impl CaptureRecord {
+    /// Decodes the recorded frame the way the link does.
+    pub fn decode(&self) -> Result<GameServerMessage, ProtocolError> {
+        match (&self.text, &self.binary) {
+            (Some(text), _) => GameServerMessage::from_json(text),
+            (None, Some(binary)) => {
+                let bytes = hex::decode(binary).map_err(|e| ProtocolError::Malformed(format!("invalid hex: {}", e)))?;
+                GameServerMessage::from_binary(&bytes)
+            }
+            (None, None) => Err(ProtocolError::Malformed("empty capture record".to_string())),
+        }
+    }
+}
+
+/// Appends every frame of the game server links to a JSONL file.
+///
+/// Records are written by a dedicated thread, so the link tasks never wait on the disk.
+pub struct Recorder {
+    started: Instant,
+    lines: mpsc::Sender<String>,
+}
+
+impl Recorder {
+    /// Creates the capture file, truncating any previous recording.
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("cannot create {}: {}", path, e))?;
+        let (lines, received) = mpsc::channel::<String>();
+        std::thread::Builder::new()
+            .name("ds-capture".to_string())
+            .spawn(move || {
+                let mut file = LineWriter::new(file);
+                for line in received {
+                    if let Err(e) = writeln!(file, "{}", line) {
+                        error!("Failed to write the game server capture: {}", e);
+                    }
+                }
+            })
+            .map_err(|e| format!("cannot start the capture writer: {}", e))?;
+        Ok(Self {
+            started: Instant::now(),
+            lines,
+        })
+    }
+
+    /// Records a text or binary frame, control frames are skipped.
+    pub fn record(&self, region: &str, direction: Direction, frame: &Message) {
+        let (text, binary) = match frame {
+            Message::Text(text) => (Some(text.to_string()), None),
+            Message::Binary(bytes) => (None, Some(hex::encode(bytes))),
+            _ => return,
+        };
+        let record = CaptureRecord {
+            t_ms: self.started.elapsed().as_millis() as u64,
+            at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            region: region.to_string(),
+            direction,
+            text,
+            binary,
+        };
+        let line = serde_json::to_string(&record).expect("capture record is always serializable");
+        // the writer thread only stops if the file cannot be written anymore
+        let _ = self.lines.send(line);
+    }
+}
+
+/// Reads a capture file back, one record at a time.
+pub struct Replay {
+    lines: Lines<BufReader<tokio::fs::File>>,
+}
+
+impl Replay {
+    pub async fn open(path: &str) -> Result<Self, String> {
+        let file = tokio::fs::File::open(path).await.map_err(|e| format!("cannot open {}: {}", path, e))?;
+        Ok(Self {
+            lines: BufReader::new(file).lines(),
+        })
+    }
+
+    /// Next frame received from a game server, decoded. Frames sent by Horizon, frames
+    /// handled by the link itself (handshake, welcome, pong, time requests, acks),
+    /// malformed frames and invalid lines are skipped.
+    pub async fn next_inbound(&mut self) -> Option<(CaptureRecord, GameServerMessage)> {
+        loop {
+            let line = match self.lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => return None,
+                Err(e) => {
+                    error!("Failed to read the game server capture: {}", e);
+                    return None;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: CaptureRecord = match serde_json::from_str(&line) {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!("Skipping invalid capture record: {}", e);
+                    continue;
+                }
+            };
+            if record.direction != Direction::In {
+                continue;
+            }
+            let message = match record.decode() {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!("Skipping frame rejected when it was received at {} ms: {}", record.t_ms, e);
+                    continue;
+                }
+            };
+            let link_level = matches!(
+                message,
+                GameServerMessage::AuthResponse(_)
+                    | GameServerMessage::Welcome(_)
+                    | GameServerMessage::Pong(_)
+                    | GameServerMessage::TimeRequest(_)
+                    | GameServerMessage::Ack(_)
+                    | GameServerMessage::Nack(_)
+            );
+            if !link_level {
+                return Some((record, message));
+            }
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::protocol::WireFormat;
+
+/// Name of the settings file looked up next to the plugin library.
+const CONFIG_FILE_NAME: &str = "ds_game_server.toml";
+/// Environment variable pointing to an explicit settings file.
+const CONFIG_PATH_ENV: &str = "DS_GAME_SERVER_CONFIG";
+
+/// Settings of the ds_game_server plugin.
+///
+/// Loaded from `ds_game_server.toml` next to the plugin library (or the file pointed
+/// by `DS_GAME_SERVER_CONFIG`), then overridden by `DS_GAME_SERVER_*` environment
+/// variables. Every field has a default so the file is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginConfig {
+    pub game_server: GameServerConfig,
+    /// Game server instances and the part of the world each one simulates. Empty means
+    /// a single instance at `game_server.urls` simulating everything.
+    pub regions: Vec<RegionConfig>,
+    pub handoff: HandoffConfig,
+    pub capture: CaptureConfig,
+    pub movement: MovementConfig,
+    pub actions: ActionsConfig,
+    pub metrics: MetricsConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameServerConfig {
+    /// Game server WebSocket URLs, tried in order when a connection fails.
+    pub urls: Vec<String>,
+    pub connect_timeout_ms: u64,
+    pub write_timeout_ms: u64,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Number of reliable messages the outbox holds while the link is slow or down,
+    /// newer ones are dropped (spawns are reported failed). It warns at three quarters.
+    pub max_queued_messages: usize,
+    /// Encoding offered for high-frequency messages, `json` is meant for debugging.
+    pub wire_format: WireFormat,
+    /// Send player moves as one `players`/`move` per server tick instead of one
+    /// `player`/`move` per input, off by default: only for game servers that
+    /// understand it.
+    pub batch_moves: bool,
+    /// Horizon `tick_interval_ms`. Moves are flushed on every `server_tick`, or after
+    /// two intervals without one.
+    pub tick_interval_ms: u64,
+    /// Interval between two `server`/`ping`, 0 (the default) disables the heartbeat:
+    /// a game server that does not answer them would be dropped as silent.
+    pub heartbeat_interval_ms: u64,
+    /// Time without any frame from the game server after which the link is
+    /// considered dead and reconnected.
+    pub heartbeat_timeout_ms: u64,
+    /// Time the game server has to ack a spawn once it was written. 0, the default,
+    /// for game servers that do not send acks: spawns are then never rolled back.
+    pub ack_timeout_ms: u64,
+    /// Secret shared with the game servers, each side proves it knows it with an
+    /// HMAC over a fresh nonce before any game traffic. Empty disables the handshake.
+    pub shared_secret: String,
+    /// PEM file of the CAs trusted for `wss://` URLs, the system ones when empty.
+    pub tls_ca_path: String,
+    /// PEM client certificate and private key, for game servers requiring mutual TLS.
+    pub tls_cert_path: String,
+    pub tls_key_path: String,
+}
+
+impl Default for GameServerConfig {
+    fn default() -> Self {
+        Self {
+            urls: vec!["ws://host.docker.internal:8980".to_string()],
+            connect_timeout_ms: 5000,
+            write_timeout_ms: 2000,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30000,
+            max_queued_messages: 1024,
+            wire_format: WireFormat::MessagePack,
+            batch_moves: false,
+            tick_interval_ms: 33,
+            heartbeat_interval_ms: 0,
+            heartbeat_timeout_ms: 5000,
+            ack_timeout_ms: 0,
+            shared_secret: String::new(),
+            tls_ca_path: String::new(),
+            tls_cert_path: String::new(),
+            tls_key_path: String::new(),
+        }
+    }
+}
+
+impl GameServerConfig {
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_millis(self.connect_timeout_ms)
+    }
+
+    pub fn write_timeout(&self) -> Duration {
+        Duration::from_millis(self.write_timeout_ms)
+    }
+
+    pub fn initial_backoff(&self) -> Duration {
+        Duration::from_millis(self.initial_backoff_ms)
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff_ms)
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        Duration::from_millis(self.tick_interval_ms)
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_millis(self.heartbeat_interval_ms)
+    }
+
+    pub fn heartbeat_timeout(&self) -> Duration {
+        Duration::from_millis(self.heartbeat_timeout_ms)
+    }
+
+    pub fn ack_timeout(&self) -> Duration {
+        Duration::from_millis(self.ack_timeout_ms)
+    }
+}
+
+/// One game server instance and the part of the world it owns.
+///
+/// Something belongs to the first region whose box contains its position, planets
+/// can also be given by name. A region without box is the default one, owning
+/// whatever no other region claims.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegionConfig {
+    pub name: String,
+    /// Game server WebSocket URLs of this instance, tried in order.
+    pub urls: Vec<String>,
+    /// Names of the planets simulated by this instance.
+    pub planets: Vec<String>,
+    /// Corners of the box owned by this instance, in world coordinates.
+    pub min: Option<[f64; 3]>,
+    pub max: Option<[f64; 3]>,
+}
+
+impl RegionConfig {
+    pub fn contains(&self, position: [f64; 3]) -> bool {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => (0..3).all(|i| min[i] <= position[i] && position[i] < max[i]),
+            _ => false,
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.min.is_none() || self.max.is_none()
+    }
+}
+
+/// Transfer of players crossing from one region to another.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HandoffConfig {
+    pub enabled: bool,
+    /// Time both instances have to complete a handoff before it is aborted and the
+    /// player stays where it was.
+    pub timeout_ms: u64,
+}
+
+impl Default for HandoffConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_ms: 2000,
+        }
+    }
+}
+
+impl HandoffConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+/// Recording and replay of the game server traffic, to reproduce bugs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    /// JSONL file every frame exchanged with the game servers is written to, empty
+    /// disables the recording.
+    pub record_path: String,
+    /// Recording fed to the plugins instead of connecting to the game servers.
+    pub replay_path: String,
+    /// Replay with the recorded timing, false replays as fast as possible.
+    pub replay_realtime: bool,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            record_path: String::new(),
+            replay_path: String::new(),
+            replay_realtime: true,
+        }
+    }
+}
+
+/// Checks of the player moves and of the positions computed from them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MovementConfig {
+    pub enabled: bool,
+    /// Moves a player may send per second, on average; the extra ones are dropped.
+    pub max_inputs_per_second: u32,
+    /// Fastest a player can go in meters per second, jumps included. A faster move
+    /// between two reported positions is reported as a violation.
+    pub max_speed: f64,
+}
+
+impl Default for MovementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_inputs_per_second: 60,
+            max_speed: 20.0,
+        }
+    }
+}
+
+/// Player actions accepted from the clients (`player`/`action`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActionsConfig {
+    /// Known actions players are allowed to use.
+    pub enabled: Vec<String>,
+    /// Minimum time between two uses of an action by the same player, overriding the
+    /// built-in cooldown of that action.
+    pub cooldowns_ms: HashMap<String, u64>,
+}
+
+impl Default for ActionsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: vec!["jump".to_string(), "spawn_box50cm".to_string()],
+            cooldowns_ms: HashMap::new(),
+        }
+    }
+}
+
+/// Prometheus metrics of the game server links.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Address serving `GET /metrics`, such as `127.0.0.1:9101`; empty disables the
+    /// metrics.
+    pub listen: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    pub directory: String,
+    pub file_name: String,
+    /// One of `trace`, `debug`, `info`, `warn`, `error` or `off`.
+    pub level: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            directory: "logs".to_string(),
+            file_name: "ds_game_server.log".to_string(),
+            level: "debug".to_string(),
+        }
+    }
+}
+
+impl PluginConfig {
+    /// Loads the settings file if any, then applies environment overrides.
+    /// A missing or invalid file falls back to the defaults.
+    pub fn load() -> Self {
+        let mut config = match config_path() {
+            Some(path) if path.exists() => Self::from_file(&path).unwrap_or_else(|e| {
+                warn!("🔧 DsGameServerPlugin: Ignoring invalid config {}: {}", path.display(), e);
+                Self::default()
+            }),
+            _ => Self::default(),
+        };
+        config.apply_env_overrides();
+        config
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let config = toml::from_str(&content).map_err(|e| e.to_string())?;
+        info!("🔧 DsGameServerPlugin: Loaded config from {}", path.display());
+        Ok(config)
+    }
+
+    fn apply_env_overrides(&mut self) {
+        if let Ok(urls) = std::env::var("DS_GAME_SERVER_URLS") {
+            let urls: Vec<String> = urls
+                .split(',')
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty())
+                .collect();
+            if !urls.is_empty() {
+                self.game_server.urls = urls;
+            }
+        }
+        override_from_env("DS_GAME_SERVER_CONNECT_TIMEOUT_MS", &mut self.game_server.connect_timeout_ms);
+        override_from_env("DS_GAME_SERVER_WRITE_TIMEOUT_MS", &mut self.game_server.write_timeout_ms);
+        override_from_env("DS_GAME_SERVER_INITIAL_BACKOFF_MS", &mut self.game_server.initial_backoff_ms);
+        override_from_env("DS_GAME_SERVER_MAX_BACKOFF_MS", &mut self.game_server.max_backoff_ms);
+        override_from_env("DS_GAME_SERVER_MAX_QUEUED_MESSAGES", &mut self.game_server.max_queued_messages);
+        override_from_env("DS_GAME_SERVER_WIRE_FORMAT", &mut self.game_server.wire_format);
+        override_from_env("DS_GAME_SERVER_BATCH_MOVES", &mut self.game_server.batch_moves);
+        override_from_env("DS_GAME_SERVER_TICK_INTERVAL_MS", &mut self.game_server.tick_interval_ms);
+        override_from_env("DS_GAME_SERVER_HEARTBEAT_INTERVAL_MS", &mut self.game_server.heartbeat_interval_ms);
+        override_from_env("DS_GAME_SERVER_HEARTBEAT_TIMEOUT_MS", &mut self.game_server.heartbeat_timeout_ms);
+        override_from_env("DS_GAME_SERVER_ACK_TIMEOUT_MS", &mut self.game_server.ack_timeout_ms);
+        override_from_env("DS_GAME_SERVER_SHARED_SECRET", &mut self.game_server.shared_secret);
+        override_from_env("DS_GAME_SERVER_TLS_CA_PATH", &mut self.game_server.tls_ca_path);
+        override_from_env("DS_GAME_SERVER_TLS_CERT_PATH", &mut self.game_server.tls_cert_path);
+        override_from_env("DS_GAME_SERVER_TLS_KEY_PATH", &mut self.game_server.tls_key_path);
+        override_from_env("DS_GAME_SERVER_HANDOFF_ENABLED", &mut self.handoff.enabled);
+        override_from_env("DS_GAME_SERVER_HANDOFF_TIMEOUT_MS", &mut self.handoff.timeout_ms);
+        override_from_env("DS_GAME_SERVER_RECORD_PATH", &mut self.capture.record_path);
+        override_from_env("DS_GAME_SERVER_REPLAY_PATH", &mut self.capture.replay_path);
+        override_from_env("DS_GAME_SERVER_REPLAY_REALTIME", &mut self.capture.replay_realtime);
+        override_from_env("DS_GAME_SERVER_MOVEMENT_ENABLED", &mut self.movement.enabled);
+        override_from_env("DS_GAME_SERVER_MAX_INPUTS_PER_SECOND", &mut self.movement.max_inputs_per_second);
+        override_from_env("DS_GAME_SERVER_MAX_SPEED", &mut self.movement.max_speed);
+        if let Ok(enabled) = std::env::var("DS_GAME_SERVER_ACTIONS_ENABLED") {
+            self.actions.enabled = enabled
+                .split(',')
+                .map(|action| action.trim().to_string())
+                .filter(|action| !action.is_empty())
+                .collect();
+        }
+        override_from_env("DS_GAME_SERVER_METRICS_LISTEN", &mut self.metrics.listen);
+        override_from_env("DS_GAME_SERVER_LOG_DIR", &mut self.logging.directory);
+        override_from_env("DS_GAME_SERVER_LOG_FILE", &mut self.logging.file_name);
+        override_from_env("DS_GAME_SERVER_LOG_LEVEL", &mut self.logging.level);
+    }
+}
+
+/// Replaces `value` with the parsed environment variable, if set and valid.
+fn override_from_env<T: FromStr>(name: &str, value: &mut T) {
+    if let Ok(raw) = std::env::var(name) {
+        match raw.parse() {
+            Ok(parsed) => *value = parsed,
+            Err(_) => warn!("🔧 DsGameServerPlugin: Ignoring invalid value for {}: {}", name, raw),
+        }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var(CONFIG_PATH_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    plugin_dir().map(|dir| dir.join(CONFIG_FILE_NAME))
+}
+
+/// Directory of the shared library this plugin was loaded from.
+#[cfg(unix)]
+fn plugin_dir() -> Option<PathBuf> {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    // any symbol of this library resolves to the library file
+    let symbol = plugin_dir as *const libc::c_void;
+    if unsafe { libc::dladdr(symbol, &mut info) } == 0 || info.dli_fname.is_null() {
+        return None;
+    }
+    let file = unsafe { std::ffi::CStr::from_ptr(info.dli_fname) };
+    let file = PathBuf::from(file.to_str().ok()?);
+    file.parent().map(Path::to_path_buf)
+}
+
+#[cfg(not(unix))]
+fn plugin_dir() -> Option<PathBuf> {
+    Some(PathBuf::from("plugins"))
+}
@@ -0,0 +1,179 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::HandoffConfig;
+use crate::protocol::{GameServerMessage, HandoffData, HandoffStateData};
+use crate::regions::{position_of, Regions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// `handoff`/`freeze` sent to the old instance, waiting for the player state.
+    Freezing,
+    /// `handoff`/`prepare` sent to the new instance, waiting for `handoff`/`ready`.
+    Preparing,
+}
+
+struct Handoff {
+    player_uuid: String,
+    from: usize,
+    to: usize,
+    phase: Phase,
+    started: Instant,
+}
+
+/// Two-phase transfer of players crossing from one region to another.
+///
+/// The old instance freezes the player and reports its exact state, the new one
+/// spawns it hidden, then both are told to commit: the player is owned by exactly
+/// one instance at any time and only its positions are forwarded. Any failure
+/// aborts the handoff and the player stays on the old instance.
+pub struct Handoffs {
+    config: HandoffConfig,
+    pending: Mutex<HashMap<String, Handoff>>,
+    /// Players whose last handoff was aborted, not retried before this instant.
+    retry_after: Mutex<HashMap<String, Instant>>,
+}
+
+impl Handoffs {
+    pub fn new(config: HandoffConfig) -> Self {
+        Self {
+            config,
+            pending: Mutex::new(HashMap::new()),
+            retry_after: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn in_progress(pending: &HashMap<String, Handoff>, player_uuid: &str) -> bool {
+        pending.values().any(|handoff| handoff.player_uuid == player_uuid)
+    }
+
+    /// Starts the handoff of the players a region reports outside of its area.
+    pub fn check_crossings(&self, regions: &Regions, index: usize, players: &Value) {
+        if !self.config.enabled || regions.all().len() == 1 {
+            return;
+        }
+        let Some(players) = players.as_array() else {
+            return;
+        };
+        let mut pending = self.pending.lock().unwrap();
+        let mut retry_after = self.retry_after.lock().unwrap();
+        retry_after.retain(|_, at| *at > Instant::now());
+        for player in players {
+            let Some(player_uuid) = player["uuid"].as_str() else {
+                continue;
+            };
+            if regions.player_region(player_uuid) != index
+                || Self::in_progress(&pending, player_uuid)
+                || retry_after.contains_key(player_uuid)
+            {
+                continue;
+            }
+            let to = regions.region_at(position_of(player));
+            if to == index {
+                continue;
+            }
+            let handoff_id = Uuid::new_v4().to_string();
+            info!(
+                "🔧 DsGameServerPlugin: Handing off player {} from region {} to {} ({})",
+                player_uuid,
+                regions.get(index).config.name,
+                regions.get(to).config.name,
+                handoff_id
+            );
+            regions.get(index).link.send_message(&GameServerMessage::HandoffFreeze(HandoffData {
+                handoff_id: handoff_id.clone(),
+                player_uuid: player_uuid.to_string(),
+            }));
+            pending.insert(
+                handoff_id,
+                Handoff {
+                    player_uuid: player_uuid.to_string(),
+                    from: index,
+                    to,
+                    phase: Phase::Freezing,
+                    started: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// The old instance froze the player: forwards its state to the new one.
+    pub fn on_state(&self, regions: &Regions, index: usize, data: HandoffStateData) {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(handoff) = pending.get_mut(&data.handoff_id) else {
+            warn!("🔧 DsGameServerPlugin: Ignoring state of unknown handoff {}", data.handoff_id);
+            return;
+        };
+        if handoff.from != index || handoff.phase != Phase::Freezing {
+            warn!("🔧 DsGameServerPlugin: Ignoring unexpected state of handoff {}", data.handoff_id);
+            return;
+        }
+        handoff.phase = Phase::Preparing;
+        regions.get(handoff.to).link.send_message(&GameServerMessage::HandoffPrepare(data));
+    }
+
+    /// The new instance spawned the player: both instances commit and the new one
+    /// becomes its owner.
+    pub fn on_ready(&self, regions: &Regions, index: usize, data: HandoffData) {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.get(&data.handoff_id) {
+            Some(handoff) if handoff.to == index && handoff.phase == Phase::Preparing => {}
+            _ => {
+                warn!("🔧 DsGameServerPlugin: Ignoring unexpected ready of handoff {}", data.handoff_id);
+                return;
+            }
+        }
+        let handoff = pending.remove(&data.handoff_id).unwrap();
+        regions.move_player(&handoff.player_uuid, handoff.to);
+        let commit = GameServerMessage::HandoffCommit(data);
+        regions.get(handoff.from).link.send_message(&commit);
+        regions.get(handoff.to).link.send_message(&commit);
+        info!(
+            "🔧 DsGameServerPlugin: Player {} handed off to region {} in {:?}",
+            handoff.player_uuid,
+            regions.get(handoff.to).config.name,
+            handoff.started.elapsed()
+        );
+    }
+
+    /// Aborts the handoffs that did not complete in time.
+    pub fn abort_expired(&self, regions: &Regions) {
+        let timeout = self.config.timeout();
+        self.abort_where(regions, "timed out", |handoff| handoff.started.elapsed() > timeout);
+    }
+
+    /// Aborts the handoffs involving a region whose link is down.
+    pub fn link_lost(&self, regions: &Regions, index: usize) {
+        self.abort_where(regions, "link down", |handoff| handoff.from == index || handoff.to == index);
+    }
+
+    /// Aborts the handoff of a player who left.
+    pub fn forget_player(&self, regions: &Regions, player_uuid: &str) {
+        self.abort_where(regions, "player disconnected", |handoff| handoff.player_uuid == player_uuid);
+        self.retry_after.lock().unwrap().remove(player_uuid);
+    }
+
+    fn abort_where(&self, regions: &Regions, reason: &str, matches: impl Fn(&Handoff) -> bool) {
+        let mut pending = self.pending.lock().unwrap();
+        let mut retry_after = self.retry_after.lock().unwrap();
+        let aborted: Vec<String> = pending.iter().filter(|(_, h)| matches(h)).map(|(id, _)| id.clone()).collect();
+        for handoff_id in aborted {
+            let handoff = pending.remove(&handoff_id).unwrap();
+            warn!("🔧 DsGameServerPlugin: Handoff {} of player {} aborted: {}", handoff_id, handoff.player_uuid, reason);
+            // the player is still across the border, give the instances time to recover
+            retry_after.insert(handoff.player_uuid.clone(), Instant::now() + self.config.timeout());
+            let abort = GameServerMessage::HandoffAbort(HandoffData {
+                handoff_id,
+                player_uuid: handoff.player_uuid,
+            });
+            regions.get(handoff.from).link.send_message(&abort);
+            if handoff.phase == Phase::Preparing {
+                regions.get(handoff.to).link.send_message(&abort);
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Default)]
+struct PlayerInputs {
+    /// `seq` of the latest move forwarded to the game server.
+    received: u64,
+    /// `seq` of the latest move reflected by the positions sent to the clients.
+    processed: u64,
+}
+
+/// Sequence numbers of the player moves, so that clients predicting their own
+/// movement can reconcile it with the authoritative positions.
+///
+/// Clients number their `update_position` inputs with an increasing `seq`. Each player
+/// of a position update then carries the `last_seq` it reflects: the one echoed by the
+/// game server when it does, the latest forwarded input otherwise.
+pub struct InputSequences {
+    /// Keyed by props `Player.uuid`, as the positions are.
+    players: Mutex<HashMap<String, PlayerInputs>>,
+}
+
+impl InputSequences {
+    pub fn new() -> Self {
+        Self {
+            players: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the `seq` of a move, or why it must be dropped as stale. Moves without
+    /// `seq` pass as they are, for clients that do not reconcile.
+    pub fn check(&self, player_uuid: &str, data: &Value) -> Result<Option<u64>, String> {
+        let Some(seq) = data.get("seq") else {
+            return Ok(None);
+        };
+        let seq = seq.as_u64().ok_or_else(|| format!("invalid seq {}", seq))?;
+        let players = self.players.lock().unwrap();
+        let received = players.get(player_uuid).map_or(0, |inputs| inputs.received);
+        if seq <= received {
+            return Err(format!("stale input {}, already at {}", seq, received));
+        }
+        Ok(Some(seq))
+    }
+
+    /// Records the `seq` of a move forwarded to the game server. Only forwarded moves
+    /// count, `last_seq` must never tell a client a dropped move was applied.
+    pub fn record(&self, player_uuid: &str, seq: u64) {
+        let mut players = self.players.lock().unwrap();
+        let inputs = players.entry(player_uuid.to_string()).or_default();
+        inputs.received = inputs.received.max(seq);
+    }
+
+    /// Sets the `last_seq` of the players of a `players`/`position` update.
+    pub fn stamp(&self, players: &mut Value) {
+        let Some(players) = players.as_array_mut() else {
+            return;
+        };
+        let mut inputs = self.players.lock().unwrap();
+        for player in players {
+            let Some(inputs) = player["uuid"].as_str().and_then(|uuid| inputs.get_mut(uuid)) else {
+                continue;
+            };
+            let processed = match player["last_seq"].as_u64() {
+                // never ahead of what the client sent, never back after a handoff
+                Some(echoed) => echoed.min(inputs.received),
+                None => inputs.received,
+            };
+            inputs.processed = inputs.processed.max(processed);
+            player["last_seq"] = inputs.processed.into();
+        }
+    }
+
+    pub fn forget(&self, player_uuid: &str) {
+        self.players.lock().unwrap().remove(player_uuid);
+    }
+}
+
+impl Default for InputSequences {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn only_forwarded_moves_advance_the_seq() {
+        let inputs = InputSequences::new();
+        assert_eq!(inputs.check("alice", &json!({ "seq": 3 })), Ok(Some(3)));
+        // dropped later on, for instance by the rate limit
+        assert_eq!(inputs.check("alice", &json!({ "seq": 3 })), Ok(Some(3)));
+
+        inputs.record("alice", 3);
+        assert!(inputs.check("alice", &json!({ "seq": 3 })).is_err());
+        assert!(inputs.check("alice", &json!({ "seq": 2 })).is_err());
+        assert_eq!(inputs.check("alice", &json!({ "seq": 4 })), Ok(Some(4)));
+        // other players have their own sequence
+        assert_eq!(inputs.check("bob", &json!({ "seq": 1 })), Ok(Some(1)));
+    }
+
+    #[test]
+    fn moves_without_seq_pass_and_bad_seq_is_refused() {
+        let inputs = InputSequences::new();
+        assert_eq!(inputs.check("alice", &json!({ "dir": { "x": 1.0, "y": 0.0, "z": 0.0 } })), Ok(None));
+        assert!(inputs.check("alice", &json!({ "seq": "one" })).is_err());
+        assert!(inputs.check("alice", &json!({ "seq": -1 })).is_err());
+    }
+
+    #[test]
+    fn stamp_never_exceeds_the_forwarded_seq_nor_goes_back() {
+        let inputs = InputSequences::new();
+        inputs.record("alice", 10);
+
+        let mut players = json!([{ "uuid": "alice", "last_seq": 12 }, { "uuid": "bob", "last_seq": 4 }]);
+        inputs.stamp(&mut players);
+        assert_eq!(players[0]["last_seq"], 10);
+        // unknown players keep what the game server reported
+        assert_eq!(players[1]["last_seq"], 4);
+
+        // an instance joined after a handoff reports an older seq
+        let mut players = json!([{ "uuid": "alice", "last_seq": 7 }]);
+        inputs.stamp(&mut players);
+        assert_eq!(players[0]["last_seq"], 10);
+
+        // without last_seq, the latest forwarded move
+        inputs.record("alice", 11);
+        let mut players = json!([{ "uuid": "alice" }]);
+        inputs.stamp(&mut players);
+        assert_eq!(players[0]["last_seq"], 11);
+    }
+}
@@ -1,29 +1,48 @@
 use async_trait::async_trait;
 use horizon_event_system::{
-    create_simple_plugin, EventError, EventSystem, PlayerId, LogLevel, PluginError, ServerContext, SimplePlugin, ClientEventWrapper, PlayerDisconnectedEvent,
+    create_simple_plugin, EventSystem, PlayerId, LogLevel, PluginError, ServerContext, SimplePlugin, ClientEventWrapper, PlayerDisconnectedEvent,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use tracing::{info, error, debug};
+use std::time::Duration;
+use tracing::{info, debug, warn};
 use tracing_appender::rolling;
 use tracing_appender::non_blocking;
-use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Layer;
 use tracing_subscriber::util::SubscriberInitExt;
-use std::path::Path;
-
-use websocket::ClientBuilder;
-// use websocket::client::sync::Client;
-use websocket::r#async::client::{Client, ClientNew, Framed};
-// use websocket::r#async::TcpStream;
-// use websocket::stream::sync::TcpStream;
-use std::net::TcpStream;
-use websocket::message::OwnedMessage;
-use websocket::sender::Writer;
-use websocket::result::WebSocketError;
-use std::process::exit;
+
 use serde_json::json;
 
+pub mod actions;
+pub mod auth;
+pub mod capture;
+pub mod config;
+pub mod handoff;
+pub mod inputs;
+pub mod link;
+pub mod metrics;
+pub mod movement;
+pub mod outbox;
+pub mod protocol;
+pub mod regions;
+pub mod requests;
+pub mod tls;
+use crate::actions::{ActionGate, PlayerAction};
+use crate::capture::{Recorder, Replay};
+use crate::config::{CaptureConfig, PluginConfig};
+use crate::handoff::Handoffs;
+use crate::inputs::InputSequences;
+use crate::link::{GameServerLink, LinkEvent};
+use crate::metrics::Metrics;
+use crate::movement::{MovementGuard, Violation};
+use crate::regions::Regions;
+use crate::protocol::{AddPropData, AddPropsData, GameServerMessage, PlayerActionData, PlayerRemoveData, SyncWorldData};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerInit {
     pub data: PlayerInitData,
@@ -40,22 +59,220 @@ pub struct PlayerInitData {
 // DsGameServer Plugin
 pub struct DsGameServerPlugin {
     name: String,
-    socket_url: String,
-    websocket: Arc<Mutex<Option<Writer<TcpStream>>>>,
+    config: PluginConfig,
+    regions: Arc<Regions>,
+    handoffs: Arc<Handoffs>,
+    inputs: Arc<InputSequences>,
+    movement: Arc<MovementGuard>,
+    /// Props `Player.uuid` of each connected player, keyed by Horizon `PlayerId`.
+    player_uuids: Arc<Mutex<HashMap<String, String>>>,
+    actions: Arc<ActionGate>,
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl DsGameServerPlugin {
     pub fn new() -> Self {
         info!("🔧 DsGameServerPlugin: Creating new instance");
 
+        let config = PluginConfig::load();
+        let recorder = match config.capture.record_path.as_str() {
+            "" => None,
+            path => match Recorder::create(path) {
+                Ok(recorder) => {
+                    info!("🔧 DsGameServerPlugin: Recording game server traffic to {}", path);
+                    Some(Arc::new(recorder))
+                }
+                Err(e) => {
+                    warn!("🔧 DsGameServerPlugin: Not recording game server traffic: {}", e);
+                    None
+                }
+            },
+        };
+        let metrics = match config.metrics.listen.as_str() {
+            "" => None,
+            _ => match Metrics::new() {
+                Ok(metrics) => Some(Arc::new(metrics)),
+                Err(e) => {
+                    warn!("🔧 DsGameServerPlugin: Metrics disabled: {}", e);
+                    None
+                }
+            },
+        };
         Self {
             name: "ds_game_server".to_string(),
-            socket_url: "ws://host.docker.internal:8980".to_string(),
-            websocket: Arc::new(Mutex::new(None)),
+            regions: Arc::new(Regions::new(&config.game_server, &config.regions, recorder, metrics.clone())),
+            handoffs: Arc::new(Handoffs::new(config.handoff.clone())),
+            inputs: Arc::new(InputSequences::new()),
+            movement: Arc::new(MovementGuard::new(config.movement.clone())),
+            player_uuids: Arc::new(Mutex::new(HashMap::new())),
+            actions: Arc::new(ActionGate::new(config.actions.clone())),
+            metrics,
+            config,
+        }
+    }
+}
+
+impl Default for DsGameServerPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Remembers which props player a Horizon player is, from the `player` of an `add_props`.
+fn remember_player(player_uuids: &Mutex<HashMap<String, String>>, player: &serde_json::Value) {
+    if let (Some(internal_uuid), Some(uuid)) = (player["internal_uuid"].as_str(), player["uuid"].as_str()) {
+        player_uuids.lock().unwrap().insert(internal_uuid.to_string(), uuid.to_string());
+    }
+}
+
+/// Link of the instance simulating a Horizon player, the default one if it never spawned.
+fn player_link(regions: &Regions, player_uuids: &Mutex<HashMap<String, String>>, player_id: &str) -> Arc<GameServerLink> {
+    let index = match player_uuids.lock().unwrap().get(player_id) {
+        Some(player_uuid) => regions.player_region(player_uuid),
+        None => regions.default_index(),
+    };
+    Arc::clone(&regions.get(index).link)
+}
+
+/// Logs a movement violation and emits it as `gameserverplugin`/`movement_violation`
+/// for moderation.
+async fn report_violation(events: &Arc<EventSystem>, violation: Violation) {
+    warn!(
+        "🔧 DsGameServerPlugin: Movement violation of player {} ({}): {:?} {}",
+        violation.player_id.as_deref().unwrap_or("?"),
+        violation.player_uuid.as_deref().unwrap_or("?"),
+        violation.kind,
+        violation.reason
+    );
+    if let Err(e) = events.emit_plugin("gameserverplugin", "movement_violation", &violation).await {
+        tracing::error!("Failed to emit plugin event to gameserverplugin: {}", e);
+    }
+}
+
+/// Reacts to the lifecycle of the link of region `index` and routes frames received
+/// from its game server to the matching plugin event.
+async fn handle_link_event(
+    events: &Arc<EventSystem>,
+    regions: &Regions,
+    handoffs: &Handoffs,
+    inputs: &InputSequences,
+    movement: &MovementGuard,
+    index: usize,
+    event: LinkEvent,
+) {
+    let region = regions.get(index).config.name.clone();
+    match event {
+        LinkEvent::Connected { reconnect } => {
+            let payload = json!({ "region": region, "reconnect": reconnect });
+            if let Err(e) = events.emit_plugin("gameserverplugin", "link_up", &payload).await {
+                tracing::error!("Failed to emit plugin event to gameserverplugin: {}", e);
+            }
+            if reconnect {
+                // the game server may have restarted with an empty scene, ask for the whole world
+                info!("🔧 DsGameServerPlugin: Link to region {} re-established, requesting world snapshot", region);
+                regions.request_resync(index);
+                if let Err(e) = events.emit_plugin("propsplugin", "world_snapshot_request", &json!({ "region": region })).await {
+                    tracing::error!("Failed to emit plugin event to propsplugin: {}", e);
+                }
+            }
+        }
+        LinkEvent::Disconnected { reason } => {
+            warn!("🔧 DsGameServerPlugin: Game server link of region {} down: {}", region, reason);
+            regions.drop_positions(index);
+            handoffs.link_lost(regions, index);
+            let payload = json!({ "region": region, "reason": reason });
+            if let Err(e) = events.emit_plugin("gameserverplugin", "link_down", &payload).await {
+                tracing::error!("Failed to emit plugin event to gameserverplugin: {}", e);
+            }
+        }
+        LinkEvent::CommandResult { request_id, command, result } => {
+            let payload = match result {
+                Ok(()) => json!({ "request_id": request_id, "command": command, "ok": true }),
+                Err(reason) => {
+                    warn!("🔧 DsGameServerPlugin: Game server failed {} {}: {}", command, request_id, reason);
+                    json!({ "request_id": request_id, "command": command, "ok": false, "reason": reason })
+                }
+            };
+            if let Err(e) = events.emit_plugin("propsplugin", "spawn_result", &payload).await {
+                tracing::error!("Failed to emit plugin event to propsplugin: {}", e);
+            }
+        }
+        LinkEvent::CommandLost { request_id, command } => {
+            debug!("🔧 DsGameServerPlugin: Link of region {} lost before the answer to {} {}", region, command, request_id);
+            let payload = json!({ "request_id": request_id, "command": command, "ok": false, "reason": "link lost", "link_lost": true });
+            if let Err(e) = events.emit_plugin("propsplugin", "spawn_result", &payload).await {
+                tracing::error!("Failed to emit plugin event to propsplugin: {}", e);
+            }
+        }
+        LinkEvent::Message(GameServerMessage::HandoffState(data)) => handoffs.on_state(regions, index, data),
+        LinkEvent::Message(GameServerMessage::HandoffReady(data)) => handoffs.on_ready(regions, index, data),
+        LinkEvent::Message(mut message) => {
+            if let GameServerMessage::PlayersPosition(players) = &mut message {
+                handoffs.check_crossings(regions, index, players);
+                inputs.stamp(players);
+                for violation in movement.check_positions(regions, index, players) {
+                    report_violation(events, violation).await;
+                }
+            }
+            // positions of several regions are merged and forwarded on the next tick
+            if let Some(message) = regions.store_positions(index, message) {
+                handle_game_server_message(events, message).await;
+            }
         }
     }
 }
 
+/// Feeds a capture file to the plugins in place of the game server links, with the
+/// recorded timing or as fast as possible.
+async fn replay_capture(
+    events: Arc<EventSystem>,
+    regions: Arc<Regions>,
+    handoffs: Arc<Handoffs>,
+    inputs: Arc<InputSequences>,
+    movement: Arc<MovementGuard>,
+    capture: CaptureConfig,
+) {
+    let path = &capture.replay_path;
+    let mut replay = match Replay::open(path).await {
+        Ok(replay) => replay,
+        Err(e) => {
+            tracing::error!("🔧 DsGameServerPlugin: Cannot replay game server traffic: {}", e);
+            return;
+        }
+    };
+    let pace = if capture.replay_realtime { "with the recorded timing" } else { "as fast as possible" };
+    info!("🔧 DsGameServerPlugin: Replaying game server traffic from {} {}", path, pace);
+    for index in 0..regions.all().len() {
+        handle_link_event(&events, &regions, &handoffs, &inputs, &movement, index, LinkEvent::Connected { reconnect: false }).await;
+    }
+    let started = tokio::time::Instant::now();
+    let mut replayed = 0;
+    while let Some((record, message)) = replay.next_inbound().await {
+        if capture.replay_realtime {
+            tokio::time::sleep_until(started + Duration::from_millis(record.t_ms)).await;
+        }
+        let index = regions.index_of(&record.region).unwrap_or_else(|| regions.default_index());
+        handle_link_event(&events, &regions, &handoffs, &inputs, &movement, index, LinkEvent::Message(message)).await;
+        replayed += 1;
+    }
+    info!("🔧 DsGameServerPlugin: Replay of {} done, {} frames", path, replayed);
+}
+
+/// Routes a message received from the game server to the matching plugin event.
+async fn handle_game_server_message(events: &Arc<EventSystem>, message: GameServerMessage) {
+    let (event, payload) = match message {
+        GameServerMessage::PlayersPosition(data) => ("players_position_update", json!({ "players": data })),
+        GameServerMessage::PropsPosition(data) => ("props_position_update", json!({ "props": data })),
+        other => {
+            warn!("Unexpected {}/{} message from game server", other.namespace(), other.event());
+            return;
+        }
+    };
+    if let Err(e) = events.emit_plugin("propsplugin", event, &payload).await {
+        tracing::error!("Failed to emit plugin event to propsplugin: {}", e);
+    }
+}
+
 #[async_trait]
 impl SimplePlugin for DsGameServerPlugin {
     fn name(&self) -> &str {
@@ -80,8 +297,46 @@ impl SimplePlugin for DsGameServerPlugin {
         //     },
         // })?;
 
-        let url = self.socket_url.clone();
-        let websocket = Arc::clone(&self.websocket);
+        // Try to use the current Tokio runtime if available. If not, create one and keep it alive.
+        let mut owned_runtime: Option<Arc<tokio::runtime::Runtime>> = None;
+        let rt_handle = match tokio::runtime::Handle::try_current() {
+            Ok(h) => h,
+            Err(_) => {
+                let rt = Arc::new(
+                    tokio::runtime::Builder::new_multi_thread()
+                        .enable_all()
+                        .build()
+                        .map_err(|e| PluginError::ExecutionError(format!("failed to create runtime: {}", e)))?,
+                );
+                let handle = rt.handle().clone();
+                owned_runtime = Some(rt);
+                handle
+            }
+        };
+
+        if let Some(metrics) = &self.metrics {
+            let serving = metrics::serve(Arc::clone(metrics), self.config.metrics.listen.clone());
+            let owned_rt = owned_runtime.clone();
+            rt_handle.spawn(async move {
+                let _owned_rt = owned_rt;
+                serving.await;
+            });
+        }
+
+        // clones for the handlers below, `rt_handle` and `owned_runtime` move into init_server
+        let rt_handle_for_actions = rt_handle.clone();
+        let owned_runtime_for_actions = owned_runtime.clone();
+        let rt_handle_for_tick = rt_handle.clone();
+        let owned_runtime_for_tick = owned_runtime.clone();
+        let rt_handle_for_moves = rt_handle.clone();
+
+        let regions = Arc::clone(&self.regions);
+        let handoffs = Arc::clone(&self.handoffs);
+        let inputs = Arc::clone(&self.inputs);
+        let movement = Arc::clone(&self.movement);
+        let player_uuids = Arc::clone(&self.player_uuids);
+        let capture = self.config.capture.clone();
+        let replay_started = Arc::new(AtomicBool::new(false));
         let events1 = events.clone();
         // events.on_client("player", "init", move |event: PlayerInit| {
         //     println!("Receive player init message {:?}", event);
@@ -90,202 +345,270 @@ impl SimplePlugin for DsGameServerPlugin {
         //         "name": event.data.name,
         //         "spawnpoint": event.data.spawnpoint
         //     });
-        //     // self.websocket.expect("Problem for send to game server").send_message(&message).unwrap();
-
-        //     let mut ws_guard = websocket.lock().unwrap();
-        //     if let Err(e) = ws_guard
-        //         .as_mut()
-        //         .expect("Problem for send to game server")
-        //         .send_message(&OwnedMessage::Text(message.to_string()))
-        //     {
-        //         return Err(EventError::HandlerExecution(format!("Message blocked: {e}")));
-        //     }
+        //     link.send(OwnedMessage::Text(message.to_string()));
         //     Ok(())
         // }).await.unwrap();
 
-        events.on_plugin("gameserverplugin", "init_server", move |event: serde_json::Value| {
+        events.on_plugin("gameserverplugin", "init_server", move |event: AddPropsData| {
             println!("🔧 DsGameServerPlugin: Initializing server with event {:?}", event);
+            remember_player(&player_uuids, &event.player);
 
-            let url = url.clone();
-            let websocket = Arc::clone(&websocket);
-            let events2 = events1.clone();
-            let initial_event = event.clone();
-
-            std::thread::spawn(move || {
-                // Connect and store writer
-                let socket = ClientBuilder::new(&url).unwrap().connect_insecure().unwrap();
-                let (mut receiver, sender) = socket.split().unwrap();
-                *websocket.lock().unwrap() = Some(sender);
-
-                // Send initial add_props
-                let message = json!({
-                    "namespace": "server",
-                    "event": "add_props",
-                    "data": {
-                        "planets": initial_event["planets"],
-                        "player": initial_event["player"]
-                    },
-                });
-                debug!("[message][to][gamesever]: {:?}", message);
-                if let Some(w) = websocket.lock().unwrap().as_mut() {
-                    let _ = w.send_message(&OwnedMessage::Text(message.to_string()));
+            // every instance gets the planets it owns, the one owning the player gets it too
+            let player_region = regions.assign_player(&event.player);
+            let mut planets = vec![Vec::new(); regions.all().len()];
+            for planet in event.planets {
+                planets[regions.region_of_planet(&planet)].push(planet);
+            }
+            // Queue initial props, they are flushed as soon as the links are connected
+            for (index, planets) in planets.into_iter().enumerate() {
+                let link = &regions.get(index).link;
+                if index == player_region {
+                    link.send_message(&GameServerMessage::AddProps(AddPropsData {
+                        planets,
+                        player: event.player.clone(),
+                        request_id: event.request_id.clone(),
+                    }));
+                } else if !planets.is_empty() {
+                    link.send_message(&GameServerMessage::SyncWorld(SyncWorldData {
+                        planets,
+                        players: Vec::new(),
+                        boxes50cm: Vec::new(),
+                    }));
                 }
+            }
 
-                // local runtime to call async event system from this blocking thread
-                let rt = tokio::runtime::Builder::new_current_thread()
-                    .enable_all()
-                    .build()
-                    .expect("failed to build temp runtime");
-
-                println!("WebSocket reader thread started");
-                for msg in receiver.incoming_messages() {
-                    match msg {
-                        Ok(OwnedMessage::Text(s)) => {
-                            debug!("[message][from][gamesever]: {}", s);
-                            if let Ok(value) = serde_json::from_str::<serde_json::Value>(&s) {
-                                if value["namespace"] == "players" && value["event"] == "position" {
-                                    let payload = serde_json::json!({ "players": value["data"] });
-                                    let events_clone = events2.clone();
-                                    let _ = rt.block_on(async move {
-                                        if let Err(e) = events_clone.emit_plugin("propsplugin", "players_position_update", &payload).await {
-                                            tracing::error!("Failed to emit plugin event to propsplugin: {}", e);
-                                        }
-                                    });
-                                } else if value["namespace"] == "props" && value["event"] == "position" {
-                                    println!("Props position update received: {:?}", value);
-                                    let payload = serde_json::json!({ "props": value["data"] });
-                                    let events_clone = events2.clone();
-                                    let _ = rt.block_on(async move {
-                                        if let Err(e) = events_clone.emit_plugin("propsplugin", "props_position_update", &payload).await {
-                                            tracing::error!("Failed to emit plugin event to propsplugin: {}", e);
-                                        }
-                                    });
-                                }
-                            } else {
-                                debug!("Failed to parse incoming JSON: {}", s);
-                            }
-                        }
-                        Ok(OwnedMessage::Binary(b)) => {
-                            if let Ok(s) = String::from_utf8(b) {
-                                debug!("[message][from][gamesever] (binary->text): {}", s);
-                                if let Ok(value) = serde_json::from_str::<serde_json::Value>(&s) {
-                                    if value["namespace"] == "players" && value["event"] == "position" {
-                                        let payload = serde_json::json!({ "players": value["data"] });
-                                        let events_clone = events2.clone();
-                                        let _ = rt.block_on(async move {
-                                            if let Err(e) = events_clone.emit_plugin("propsplugin", "players_position_update", &payload).await {
-                                                tracing::error!("Failed to emit plugin event to propsplugin: {}", e);
-                                            }
-                                        });
-                                    }
-                                }
-                            }
-                        }
-                        Ok(_) => { /* ignore ping/pong/close frames */ }
-                        Err(WebSocketError::NoDataAvailable) => {
-                            println!("\nDisconnected!");
-                            exit(2);
-                        }
-                        Err(e) => {
-                            println!("WebSocket read error: {:?}", e);
-                            exit(2);
+            if !capture.replay_path.is_empty() {
+                // commands are still queued on the links, but nothing is ever sent
+                if !replay_started.swap(true, Ordering::SeqCst) {
+                    let replay = replay_capture(events1.clone(), Arc::clone(&regions), Arc::clone(&handoffs), Arc::clone(&inputs), Arc::clone(&movement), capture.clone());
+                    let owned_rt = owned_runtime.clone();
+                    rt_handle.spawn(async move {
+                        let _owned_rt = owned_rt;
+                        replay.await;
+                    });
+                }
+                return Ok(());
+            }
+
+            for (index, region) in regions.all().iter().enumerate() {
+                if let Some(mut link_events) = region.link.start(&rt_handle) {
+                    let events = events1.clone();
+                    let regions = Arc::clone(&regions);
+                    let handoffs = Arc::clone(&handoffs);
+                    let inputs = Arc::clone(&inputs);
+                    let movement = Arc::clone(&movement);
+                    // keep the owned runtime alive as long as the link is running (if any)
+                    let owned_rt = owned_runtime.clone();
+                    rt_handle.spawn(async move {
+                        let _owned_rt = owned_rt;
+                        while let Some(event) = link_events.recv().await {
+                            handle_link_event(&events, &regions, &handoffs, &inputs, &movement, index, event).await;
                         }
-                    }
+                    });
                 }
-            });
+            }
 
             Ok(())
         }).await.unwrap();
 
-        let websocket = Arc::clone(&self.websocket);
-        events.on_plugin("gameserverplugin", "add_props", move |event: serde_json::Value| {
+        let regions = Arc::clone(&self.regions);
+        let player_uuids = Arc::clone(&self.player_uuids);
+        events.on_plugin("gameserverplugin", "add_props", move |event: AddPropsData| {
             println!("🔧 DsGameServerPlugin: Adding props with event {:?}", event);
-            let message = json!({
-                "namespace": "server",
-                "event": "add_props",
-                "data": {
-                    "planets": [],
-                    "player": event["player"]
-                },
-            });
-            let mut ws_guard = websocket.lock().map_err(|e| EventError::HandlerExecution(format!("websocket lock error: {}", e))).unwrap();
-            debug!("[message][to][gamesever]: {:?}", message);
-            if let Some(w) = ws_guard.as_mut() {
-                if let Err(e) = w.send_message(&OwnedMessage::Text(message.to_string())) {
-                    return Err(EventError::HandlerExecution(format!("Message blocked: {}", e)));
-                }
-            } else {
-                return Err(EventError::HandlerExecution("No websocket writer available".to_string()));
-            }
+            remember_player(&player_uuids, &event.player);
+            let link = &regions.get(regions.assign_player(&event.player)).link;
+            // planets are only sent once, with init_server
+            link.send_message(&GameServerMessage::AddProps(AddPropsData {
+                planets: Vec::new(),
+                player: event.player,
+                request_id: event.request_id,
+            }));
             Ok(())
         }).await.unwrap();
 
 
-        let websocket = Arc::clone(&self.websocket);
-        events.on_plugin("gameserverplugin", "add_prop", move |event: serde_json::Value| {
+        let regions = Arc::clone(&self.regions);
+        events.on_plugin("gameserverplugin", "add_prop", move |event: AddPropData| {
             println!("🔧 DsGameServerPlugin: Adding prop with event {:?}", event);
-            let message = json!({
-                "namespace": "server",
-                "event": "add_prop",
-                "data": event,
-            });
-            let mut ws_guard = websocket.lock().map_err(|e| EventError::HandlerExecution(format!("websocket lock error: {}", e))).unwrap();
-            debug!("[message][to][gamesever]: {:?}", message);
-            if let Some(w) = ws_guard.as_mut() {
-                if let Err(e) = w.send_message(&OwnedMessage::Text(message.to_string())) {
-                    return Err(EventError::HandlerExecution(format!("Message blocked: {}", e)));
-                }
-            } else {
-                return Err(EventError::HandlerExecution("No websocket writer available".to_string()));
+            // a prop is simulated next to the player who spawned it
+            let index = regions.player_region(&event.player_uuid);
+            if let Some(prop_uuid) = event.box50cm["uuid"].as_str() {
+                regions.assign_prop(prop_uuid, index);
             }
+            regions.get(index).link.send_message(&GameServerMessage::AddProp(event));
             Ok(())
         }).await.unwrap();
 
-        let websocket = Arc::clone(&self.websocket);
+        let regions = Arc::clone(&self.regions);
+        let player_uuids = Arc::clone(&self.player_uuids);
+        events.on_plugin("gameserverplugin", "world_snapshot", move |event: SyncWorldData| {
+            for player in &event.players {
+                remember_player(&player_uuids, player);
+            }
+            // only the reconnected instances need their part of the world
+            let resync = regions.take_resync();
+            if resync.is_empty() {
+                debug!("🔧 DsGameServerPlugin: No region waiting for the world snapshot");
+                return Ok(());
+            }
+            let parts = regions.split_snapshot(event);
+            for index in resync {
+                info!("🔧 DsGameServerPlugin: Replaying world snapshot to region {}", regions.get(index).config.name);
+                regions.get(index).link.resync(parts[index].clone());
+            }
+            Ok(())
+        }).await.unwrap();
+
+        let regions = Arc::clone(&self.regions);
+        let inputs = Arc::clone(&self.inputs);
+        let movement = Arc::clone(&self.movement);
+        let player_uuids = Arc::clone(&self.player_uuids);
+        let events_for_moves = events.clone();
         events.on_client_with_connection(
             "movement",
             "update_position",
             move |wrapper: ClientEventWrapper<serde_json::Value>, _connection| {
-                info!("📝 LoggerPlugin: 🦘 Client movement from player {}", wrapper.player_id);
+                debug!("📝 LoggerPlugin: 🦘 Client movement from player {}", wrapper.player_id);
                 // println!("player movement {:?}", wrapper);
-                // println!("📝 LoggerPlugin: 🦘 Client movement");
 
-                let websocket = Arc::clone(&websocket);
+                let player_id = wrapper.player_id.to_string();
+                let player_uuid = player_uuids.lock().unwrap().get(&player_id).cloned();
+                // stale or replayed moves are dropped before they spend rate limit tokens
+                let mut seq = None;
+                if let Some(player_uuid) = &player_uuid {
+                    match inputs.check(player_uuid, &wrapper.data) {
+                        Ok(checked) => seq = checked,
+                        Err(reason) => {
+                            debug!("🔧 DsGameServerPlugin: Dropped move of player {}: {}", player_id, reason);
+                            return Ok(());
+                        }
+                    }
+                }
+                if let Err(violation) = movement.check_input(&player_id, player_uuid.as_deref(), &wrapper.data) {
+                    debug!("🔧 DsGameServerPlugin: Dropped move of player {}: {}", player_id, violation.reason);
+                    if violation.first {
+                        let events = events_for_moves.clone();
+                        rt_handle_for_moves.spawn(async move {
+                            report_violation(&events, violation).await;
+                        });
+                    }
+                    return Ok(());
+                }
+
+                // sent on the next tick, a newer move of the same player replaces it
+                player_link(&regions, &player_uuids, &player_id).send_message(&GameServerMessage::PlayerMove {
+                    player_id,
+                    data: wrapper.data,
+                });
+                if let (Some(player_uuid), Some(seq)) = (&player_uuid, seq) {
+                    inputs.record(player_uuid, seq);
+                }
+                Ok(())
+            },
+        )
+        .await
+        .map_err(|e| PluginError::ExecutionError(e.to_string()))?;
+
+        let regions = Arc::clone(&self.regions);
+        let handoffs = Arc::clone(&self.handoffs);
+        let events_for_tick = events.clone();
+        events.on_core("server_tick", move |_event: serde_json::Value| {
+            // one batch of player moves per Horizon tick
+            for region in regions.all() {
+                region.link.tick();
+            }
+            handoffs.abort_expired(&regions);
+            // and one merged update of the positions received from the instances
+            let merged = regions.take_merged_positions();
+            if !merged.is_empty() {
+                let events = events_for_tick.clone();
+                let _owned_rt = &owned_runtime_for_tick;
+                rt_handle_for_tick.spawn(async move {
+                    for message in merged {
+                        handle_game_server_message(&events, message).await;
+                    }
+                });
+            }
+            Ok(())
+        }).await.map_err(|e| PluginError::ExecutionError(e.to_string()))?;
 
-                std::thread::spawn(move || {
-                    // Parse the movement data
-                    let message = json!({
-                        "namespace": "player",
-                        "event": "move",
-                        "player_id": wrapper.player_id.to_string(),
-                        "data": wrapper.data.clone(),
+        let regions = Arc::clone(&self.regions);
+        let player_uuids = Arc::clone(&self.player_uuids);
+        let actions = Arc::clone(&self.actions);
+        let events_for_actions = events.clone();
+        events.on_client_with_connection(
+            "player",
+            "action",
+            move |wrapper: ClientEventWrapper<serde_json::Value>, connection| {
+                // keep the owned runtime alive as long as this handler (if any)
+                let _owned_rt = &owned_runtime_for_actions;
+                let player_id = wrapper.player_id.to_string();
+                let checked = serde_json::from_value::<PlayerAction>(wrapper.data.clone())
+                    .map_err(|e| format!("invalid action: {}", e))
+                    .and_then(|action| {
+                        // actions only make sense once the character exists on the game server
+                        let player_uuid = player_uuids.lock().unwrap().get(&player_id).cloned();
+                        let player_uuid = player_uuid.ok_or_else(|| "player is not spawned".to_string())?;
+                        actions.check(&player_id, &action)?;
+                        Ok((action, player_uuid))
                     });
-                    debug!("[message][to][gamesever]: {:?}", message);
-                    match websocket.lock() {
-                        Ok(mut guard) => {
-                            if let Some(w) = guard.as_mut() {
-                                if let Err(e) = w.send_message(&OwnedMessage::Text(message.to_string())) {
-                                    error!("Failed to send websocket message: {}", e);
-                                }
-                            } else {
-                                error!("No websocket writer available to send movement");
+
+                match checked {
+                    Ok((action, player_uuid)) if action.creates_prop() => {
+                        debug!("🔧 DsGameServerPlugin: Player {} action {}, handing over to props", player_id, action.name());
+                        let events = events_for_actions.clone();
+                        let payload = json!({ "player_id": wrapper.player_id, "player_uuid": player_uuid, "action": action.name() });
+                        rt_handle_for_actions.spawn(async move {
+                            if let Err(e) = events.emit_plugin("propsplugin", "player_action", &payload).await {
+                                tracing::error!("Failed to emit plugin event to propsplugin: {}", e);
                             }
-                        }
-                        Err(e) => {
-                            error!("Failed to lock websocket mutex: {}", e);
-                        }
+                        });
                     }
-                });
- 
+                    Ok((action, player_uuid)) => {
+                        debug!("🔧 DsGameServerPlugin: Player {} action {}", player_id, action.name());
+                        let link = &regions.get(regions.player_region(&player_uuid)).link;
+                        link.send_message(&GameServerMessage::PlayerAction(PlayerActionData { player_uuid, action }));
+                    }
+                    Err(reason) => {
+                        debug!("🔧 DsGameServerPlugin: Rejected action of player {}: {}", player_id, reason);
+                        let rejection = json!({ "type": "action_rejected", "action": wrapper.data["action"], "reason": reason });
+                        rt_handle_for_actions.spawn(async move {
+                            if let Err(e) = connection.respond_json(&rejection).await {
+                                tracing::error!("Failed to answer player {}: {}", connection.player_id, e);
+                            }
+                        });
+                    }
+                }
                 Ok(())
             },
         )
         .await
         .map_err(|e| PluginError::ExecutionError(e.to_string()))?;
 
+        let regions = Arc::clone(&self.regions);
+        let handoffs = Arc::clone(&self.handoffs);
+        let inputs = Arc::clone(&self.inputs);
+        let movement = Arc::clone(&self.movement);
+        let player_uuids = Arc::clone(&self.player_uuids);
+        let actions = Arc::clone(&self.actions);
         events.on_core("player_disconnected", move |event: PlayerDisconnectedEvent| {
             debug!("[disconnected]: {:?}", event);
-            println!("Player disconnected.");
+            let player_id = event.player_id.to_string();
+            for region in regions.all() {
+                region.link.discard_move(&player_id);
+            }
+            actions.forget(&player_id);
+            let player_uuid = player_uuids.lock().unwrap().remove(&player_id);
+            movement.forget_player(&player_id, player_uuid.as_deref());
+            // players that never spawned on the game server have nothing to remove
+            let Some(player_uuid) = player_uuid else {
+                return Ok(());
+            };
+            info!("🔧 DsGameServerPlugin: Player {} disconnected, removing it from the game server", player_uuid);
+            handoffs.forget_player(&regions, &player_uuid);
+            inputs.forget(&player_uuid);
+            let link = &regions.get(regions.forget_player(&player_uuid)).link;
+            link.send_message(&GameServerMessage::PlayerRemove(PlayerRemoveData { player_uuid }));
             Ok(())
         }).await.map_err(|e| PluginError::ExecutionError(e.to_string()))?;
 
@@ -301,17 +624,22 @@ impl SimplePlugin for DsGameServerPlugin {
 
         // --- plugin-specific file logger ---
         // ensure logs directory exists (optional)
-        let log_dir = "logs";
-        let _ = std::fs::create_dir_all(log_dir);
-        // never rotate, single file in logs/
-        let file_appender = rolling::never(log_dir, "ds_game_server.log");
+        let logging = &self.config.logging;
+        let _ = std::fs::create_dir_all(&logging.directory);
+        // never rotate, single file in the configured directory
+        let file_appender = rolling::never(&logging.directory, &logging.file_name);
         let (non_blocking, guard) = non_blocking(file_appender);
         // keep guard alive for program lifetime so logs flush on exit
         std::mem::forget(Box::new(guard));
+        let level = LevelFilter::from_str(&logging.level).unwrap_or_else(|_| {
+            context.log(LogLevel::Warn, &format!("🔧 DsGameServerPlugin: Unknown log level {}, using debug", logging.level));
+            LevelFilter::DEBUG
+        });
         // create a layer that writes into the file (non-ANSI)
         let file_layer = tracing_subscriber::fmt::layer()
             .with_ansi(false)
-            .with_writer(non_blocking);
+            .with_writer(non_blocking)
+            .with_filter(level);
         // Try to add the file layer to the global subscriber. If the global subscriber
         // is already initialized elsewhere this will return Err — ignore in that case.
         let _ = tracing_subscriber::registry().with(file_layer).try_init();
@@ -0,0 +1,627 @@
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, error, info, warn};
+
+use crate::auth;
+use crate::capture::{Direction, Recorder};
+use crate::config::GameServerConfig;
+use crate::metrics::LinkMetrics;
+use crate::outbox::Outbox;
+use crate::requests::PendingRequests;
+use crate::protocol::{
+    server_time_ms, AuthChallengeData, AuthProofData, GameServerMessage, HelloData, PingData, PlayerMoveData, SyncWorldData,
+    TimeRequestData, TimeResponseData, WireFormat,
+};
+use crate::tls;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Capacity of the channel carrying received messages to the plugin. When full, the
+/// reader stops reading and the game server feels the backpressure.
+const INBOUND_CAPACITY: usize = 1024;
+/// Clock synchronization requests waiting for the writer, more are dropped.
+const TIME_REQUESTS_CAPACITY: usize = 16;
+
+/// State of the connection between Horizon and the game server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    Disconnected,
+    Connecting,
+    Connected,
+}
+
+/// What the supervisor task reports to the plugin.
+#[derive(Debug)]
+pub enum LinkEvent {
+    /// The link is up. `reconnect` is false only for the very first connection.
+    Connected { reconnect: bool },
+    /// An established link was lost, closed or stopped answering heartbeats.
+    Disconnected { reason: String },
+    /// Outcome of a command sent with a `request_id`: acked, nacked or timed out.
+    CommandResult {
+        request_id: String,
+        command: &'static str,
+        result: Result<(), String>,
+    },
+    /// A command was written on a connection lost before the game server answered it.
+    /// Nothing to roll back, the world resync after the reconnect sends it again.
+    CommandLost { request_id: String, command: &'static str },
+    /// A message received from the game server.
+    Message(GameServerMessage),
+}
+
+/// Supervised WebSocket link to the game server.
+///
+/// A supervisor task connects, runs one reader and one writer for the connection
+/// until either fails, then reconnects with an exponential backoff. Outbound messages
+/// go through the [`Outbox`] and are written by the single writer task, so they are
+/// kept while the link is down and flushed as soon as it is back.
+///
+/// `wss://` URLs are connected with TLS (see [`tls::connector`]). When a shared
+/// secret is set, each connection starts with an HMAC challenge in both directions
+/// and is dropped, like a failed connection, unless the game server passes it.
+///
+/// Each connection starts in JSON and announces the configured wire format with a
+/// `server`/`hello`; high-frequency messages switch to it once the game server
+/// answers with a matching `server`/`welcome`.
+///
+/// The writer also sends a `server`/`ping` every `heartbeat_interval_ms`; the echoed
+/// `server`/`pong` gives the round trip time, and a connection silent for longer than
+/// `heartbeat_timeout_ms` is dropped and reconnected.
+///
+/// Commands carrying a `request_id` are tracked until the game server acks or nacks
+/// them, or until `ack_timeout_ms` after they were written.
+///
+/// With a [`Recorder`], every frame written or received is also captured, and with
+/// [`LinkMetrics`] counted.
+pub struct GameServerLink {
+    config: GameServerConfig,
+    /// Region name and recorder of the captured frames, if recording.
+    capture: Option<(String, Arc<Recorder>)>,
+    metrics: Option<LinkMetrics>,
+    state: Mutex<LinkState>,
+    outbox: Outbox,
+    requests: PendingRequests,
+    wire_format: Mutex<WireFormat>,
+    started: AtomicBool,
+    /// Link events sent to the plugin, set by [`GameServerLink::start`].
+    events: OnceLock<mpsc::Sender<LinkEvent>>,
+    /// Origin of the `sent_at_ms` of the pings.
+    epoch: Instant,
+    /// Last time anything was received on the current connection.
+    last_seen: Mutex<Instant>,
+    rtt: Mutex<Option<Duration>>,
+}
+
+impl GameServerLink {
+    pub fn new(config: GameServerConfig) -> Self {
+        Self {
+            outbox: Outbox::new(config.max_queued_messages),
+            requests: PendingRequests::default(),
+            config,
+            capture: None,
+            metrics: None,
+            state: Mutex::new(LinkState::Disconnected),
+            wire_format: Mutex::new(WireFormat::Json),
+            started: AtomicBool::new(false),
+            events: OnceLock::new(),
+            epoch: Instant::now(),
+            last_seen: Mutex::new(Instant::now()),
+            rtt: Mutex::new(None),
+        }
+    }
+
+    /// Captures the frames of this link, tagged with the name of its region.
+    pub fn with_recorder(mut self, region: &str, recorder: Arc<Recorder>) -> Self {
+        self.capture = Some((region.to_string(), recorder));
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: LinkMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    fn record(&self, direction: Direction, frame: &Message) {
+        if let Some((region, recorder)) = &self.capture {
+            recorder.record(region, direction, frame);
+        }
+    }
+
+    pub fn state(&self) -> LinkState {
+        *self.state.lock().unwrap()
+    }
+
+    fn set_state(&self, state: LinkState) {
+        let mut current = self.state.lock().unwrap();
+        if *current != state {
+            debug!("[link] state {:?} -> {:?}", *current, state);
+            *current = state;
+        }
+    }
+
+    /// Wire format negotiated on the current connection.
+    pub fn wire_format(&self) -> WireFormat {
+        *self.wire_format.lock().unwrap()
+    }
+
+    /// Round trip time measured by the last heartbeat, `None` until the first pong.
+    pub fn rtt(&self) -> Option<Duration> {
+        *self.rtt.lock().unwrap()
+    }
+
+    fn mark_seen(&self) {
+        *self.last_seen.lock().unwrap() = Instant::now();
+    }
+
+    fn silent_for(&self) -> Duration {
+        self.last_seen.lock().unwrap().elapsed()
+    }
+
+    fn accept_pong(&self, pong: PingData) {
+        let now = self.epoch.elapsed().as_millis() as u64;
+        let rtt = Duration::from_millis(now.saturating_sub(pong.sent_at_ms));
+        debug!("[link] heartbeat {} rtt {:?}", pong.seq, rtt);
+        if let Some(metrics) = &self.metrics {
+            metrics.rtt(rtt);
+        }
+        *self.rtt.lock().unwrap() = Some(rtt);
+    }
+
+    /// Number of messages waiting for the writer task.
+    pub fn queued(&self) -> usize {
+        self.outbox.len()
+    }
+
+    /// Hands a message to the writer task. Never blocks, safe to call from handlers.
+    ///
+    /// When the outbox is full the message is dropped, and a command carrying a
+    /// `request_id` is reported failed.
+    pub fn send_message(&self, message: &GameServerMessage) {
+        if let Some(request_id) = message.request_id() {
+            if self.config.ack_timeout_ms > 0 {
+                self.requests.track(request_id, message.event());
+            }
+        }
+        if let Err(message) = self.outbox.push(message.clone()) {
+            error!("Game server outbox full, dropping {}/{}", message.namespace(), message.event());
+            if let Some(request_id) = message.request_id() {
+                self.requests.resolve(request_id);
+                let request_id = request_id.to_string();
+                let result = Err("game server outbox full".to_string());
+                self.report(LinkEvent::CommandResult { request_id, command: message.event(), result });
+            }
+            return;
+        }
+        if !self.config.batch_moves {
+            // without batching, moves do not wait for the tick
+            self.outbox.tick();
+        }
+    }
+
+    /// Queues a world snapshot ahead of everything else, in place of the waiting
+    /// commands it already accounts for. Those with a `request_id` are reported done.
+    pub fn resync(&self, snapshot: SyncWorldData) {
+        let superseded = self.outbox.resync(GameServerMessage::SyncWorld(snapshot.clone()), |message| snapshot.supersedes(message));
+        for message in superseded {
+            debug!("[link] {}/{} superseded by the world snapshot", message.namespace(), message.event());
+            let Some(request_id) = message.request_id() else {
+                continue;
+            };
+            if let Some(command) = self.requests.resolve(request_id) {
+                let request_id = request_id.to_string();
+                self.report(LinkEvent::CommandResult { request_id, command, result: Ok(()) });
+            }
+        }
+    }
+
+    /// Sends an event to the plugin from outside the supervisor task.
+    fn report(&self, event: LinkEvent) {
+        if let Some(events) = self.events.get() {
+            if let Err(e) = events.try_send(event) {
+                warn!("Failed to report a game server link event: {}", e);
+            }
+        }
+    }
+
+    /// Drops the move of `player_id` not sent yet, once the player is gone.
+    pub fn discard_move(&self, player_id: &str) {
+        self.outbox.discard(player_id);
+    }
+
+    /// Called on every Horizon server tick: flushes the moves received since the last one.
+    pub fn tick(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.queue_depth(self.outbox.len());
+        }
+        self.outbox.tick();
+    }
+
+    /// Encodes a message with the negotiated wire format.
+    fn encode(&self, message: &GameServerMessage) -> Message {
+        if message.is_high_frequency() && self.wire_format() == WireFormat::MessagePack {
+            debug!("[message][to][gamesever] (msgpack): {:?}", message);
+            return Message::binary(message.to_msgpack());
+        }
+        let text = message.to_json();
+        debug!("[message][to][gamesever]: {}", text);
+        Message::text(text)
+    }
+
+    /// Decodes a frame received from the game server, `None` for control frames.
+    fn decode(&self, frame: Message) -> Option<GameServerMessage> {
+        let decoded = match frame {
+            Message::Text(text) => {
+                debug!("[message][from][gamesever]: {}", text.as_str());
+                GameServerMessage::from_json(text.as_str())
+            }
+            Message::Binary(bytes) => GameServerMessage::from_binary(&bytes),
+            _ => return None, // ping/pong are answered by tungstenite
+        };
+        match decoded {
+            Ok(message) => Some(message),
+            Err(e) => {
+                warn!("Rejected game server message: {}", e);
+                if let Some(metrics) = &self.metrics {
+                    metrics.parse_failed();
+                }
+                None
+            }
+        }
+    }
+
+    fn accept_welcome(&self, encoding: WireFormat) {
+        if encoding == WireFormat::MessagePack && self.config.wire_format != WireFormat::MessagePack {
+            warn!("Game server chose msgpack which was not offered, staying on json");
+            return;
+        }
+        info!("Game server link negotiated {:?} encoding", encoding);
+        *self.wire_format.lock().unwrap() = encoding;
+    }
+
+    /// Starts the supervisor task on `rt` and returns the stream of link events.
+    /// Returns `None` if the link was already started.
+    pub fn start(self: &Arc<Self>, rt: &Handle) -> Option<mpsc::Receiver<LinkEvent>> {
+        if self.started.swap(true, Ordering::SeqCst) {
+            debug!("[link] supervisor already running");
+            return None;
+        }
+        if self.config.shared_secret.is_empty() {
+            warn!("No shared_secret set, game servers at {:?} are not authenticated", self.config.urls);
+        }
+        let (events_tx, events_rx) = mpsc::channel(INBOUND_CAPACITY);
+        let _ = self.events.set(events_tx.clone());
+        rt.spawn(Arc::clone(self).supervise(events_tx));
+        Some(events_rx)
+    }
+
+    async fn supervise(self: Arc<Self>, events: mpsc::Sender<LinkEvent>) {
+        let initial_backoff = self.config.initial_backoff();
+        let max_backoff = self.config.max_backoff();
+        let mut backoff = initial_backoff;
+        let mut url_index = 0;
+        let mut reconnect = false;
+        loop {
+            let url = self.config.urls.get(url_index).cloned().unwrap_or_default();
+            self.set_state(LinkState::Connecting);
+            info!("Connecting to game server at {}", url);
+
+            let connector = if url.starts_with("wss://") {
+                match tls::connector(&self.config) {
+                    Ok(connector) => Some(connector),
+                    Err(e) => {
+                        self.connection_failed(&url, &e, backoff, &mut url_index).await;
+                        backoff = (backoff * 2).min(max_backoff);
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+            let connecting = tokio_tungstenite::connect_async_tls_with_config(url.as_str(), None, false, connector);
+            let connected = timeout(self.config.connect_timeout(), connecting).await;
+            let mut stream = match connected {
+                Ok(Ok((stream, _response))) => stream,
+                Ok(Err(e)) => {
+                    self.connection_failed(&url, &e.to_string(), backoff, &mut url_index).await;
+                    backoff = (backoff * 2).min(max_backoff);
+                    continue;
+                }
+                Err(_) => {
+                    self.connection_failed(&url, "connection timed out", backoff, &mut url_index).await;
+                    backoff = (backoff * 2).min(max_backoff);
+                    continue;
+                }
+            };
+            if !self.config.shared_secret.is_empty() {
+                let authenticated = timeout(self.config.connect_timeout(), self.authenticate(&mut stream)).await;
+                let result = authenticated.unwrap_or_else(|_| Err("handshake timed out".to_string()));
+                if let Err(e) = result {
+                    let _ = stream.close(None).await;
+                    self.connection_failed(&url, &format!("authentication failed: {}", e), backoff, &mut url_index).await;
+                    backoff = (backoff * 2).min(max_backoff);
+                    continue;
+                }
+            }
+            info!("Connected to game server at {}", url);
+            *self.wire_format.lock().unwrap() = WireFormat::Json;
+            *self.rtt.lock().unwrap() = None;
+            self.mark_seen();
+            self.set_state(LinkState::Connected);
+            if let Some(metrics) = &self.metrics {
+                metrics.connected(reconnect);
+            }
+            backoff = initial_backoff;
+
+            if events.send(LinkEvent::Connected { reconnect }).await.is_err() {
+                return; // the plugin is gone
+            }
+            reconnect = true;
+
+            let (sink, stream) = stream.split();
+            // clock sync requests skip the outbox, the writer answers them right away
+            let (time_tx, time_rx) = mpsc::channel(TIME_REQUESTS_CAPACITY);
+            let result = tokio::select! {
+                result = self.write_loop(sink, time_rx, &events) => result,
+                result = self.read_loop(stream, time_tx, &events) => result,
+            };
+            let reason = match result {
+                Ok(()) => {
+                    info!("Game server closed the connection");
+                    "closed by the game server".to_string()
+                }
+                Err(e) => {
+                    warn!("Game server link failed: {}", e);
+                    e
+                }
+            };
+
+            self.set_state(LinkState::Disconnected);
+            if let Some(metrics) = &self.metrics {
+                metrics.disconnected();
+            }
+            for (request_id, command) in self.requests.connection_lost() {
+                if events.send(LinkEvent::CommandLost { request_id, command }).await.is_err() {
+                    return; // the plugin is gone
+                }
+            }
+            if events.send(LinkEvent::Disconnected { reason }).await.is_err() {
+                return; // the plugin is gone
+            }
+            warn!("Disconnected from game server, reconnecting in {:?}", backoff);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(max_backoff);
+        }
+    }
+
+    /// Proves to each other that Horizon and the game server share the secret, before
+    /// any game traffic is sent or accepted on the connection.
+    async fn authenticate(&self, stream: &mut WsStream) -> Result<(), String> {
+        let secret = &self.config.shared_secret;
+        let nonce = auth::nonce();
+        let challenge = GameServerMessage::AuthChallenge(AuthChallengeData { nonce: nonce.clone() });
+        let frame = Message::text(challenge.to_json());
+        self.record(Direction::Out, &frame);
+        stream.send(frame).await.map_err(|e| e.to_string())?;
+
+        let response = loop {
+            match stream.next().await {
+                Some(Ok(Message::Text(text))) => {
+                    self.record(Direction::In, &Message::Text(text.clone()));
+                    break GameServerMessage::from_json(text.as_str()).map_err(|e| e.to_string())?;
+                }
+                Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => continue,
+                Some(Ok(frame)) => return Err(format!("unexpected frame {:?}", frame)),
+                Some(Err(e)) => return Err(e.to_string()),
+                None => return Err("closed by the game server".to_string()),
+            }
+        };
+        let GameServerMessage::AuthResponse(response) = response else {
+            return Err(format!("expected server/auth_response, got {}/{}", response.namespace(), response.event()));
+        };
+        if response.nonce.is_empty() || !auth::verify(secret, auth::GAME_SERVER_LABEL, &nonce, &response.nonce, &response.mac) {
+            return Err("invalid game server MAC".to_string());
+        }
+
+        let mac = auth::sign(secret, auth::HORIZON_LABEL, &response.nonce, &nonce);
+        let proof = GameServerMessage::AuthProof(AuthProofData { mac });
+        let frame = Message::text(proof.to_json());
+        self.record(Direction::Out, &frame);
+        stream.send(frame).await.map_err(|e| e.to_string())?;
+        debug!("[link] game server authenticated");
+        Ok(())
+    }
+
+    async fn connection_failed(&self, url: &str, reason: &str, backoff: Duration, url_index: &mut usize) {
+        self.set_state(LinkState::Disconnected);
+        warn!("Game server connection to {} failed: {}, retrying in {:?}", url, reason, backoff);
+        // fail over to the next configured game server
+        *url_index = (*url_index + 1) % self.config.urls.len().max(1);
+        tokio::time::sleep(backoff).await;
+    }
+
+    /// Single writer of the connection: says hello, then drains the outbox whenever a
+    /// reliable message is pushed, and the moves on every tick. Unsent reliable messages
+    /// stay queued for the next connection.
+    ///
+    /// Clock synchronization requests are answered first, stamped with the Horizon
+    /// clock just before the answer is written.
+    ///
+    /// The heartbeat interval also times out the commands that were never acked.
+    async fn write_loop(
+        &self,
+        mut sink: SplitSink<WsStream, Message>,
+        mut time_requests: mpsc::Receiver<(TimeRequestData, u64)>,
+        events: &mpsc::Sender<LinkEvent>,
+    ) -> Result<(), String> {
+        let mut encodings = vec![WireFormat::Json];
+        if self.config.wire_format == WireFormat::MessagePack {
+            encodings.insert(0, WireFormat::MessagePack);
+        }
+        let hello = GameServerMessage::Hello(HelloData { encodings });
+        self.write(&mut sink, &hello).await?;
+
+        // flush anyway if Horizon stops ticking
+        let tick_fallback = self.config.tick_interval() * 2;
+        let heartbeat_enabled = self.config.heartbeat_interval_ms > 0;
+        let heartbeat_interval = if heartbeat_enabled { self.config.heartbeat_interval() } else { Duration::from_secs(1) };
+        let mut heartbeat = tokio::time::interval(heartbeat_interval);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut seq = 0;
+        loop {
+            while let Some((id, message)) = self.outbox.next() {
+                self.write(&mut sink, &message).await?;
+                self.outbox.written(id);
+                if let Some(request_id) = message.request_id() {
+                    self.requests.written(request_id);
+                }
+            }
+            tokio::select! {
+                biased;
+                Some((request, received_ms)) = time_requests.recv() => {
+                    let response = TimeResponseData { t0: request.t0, t1: received_ms, t2: server_time_ms() };
+                    self.write(&mut sink, &GameServerMessage::TimeResponse(response)).await?;
+                }
+                _ = self.outbox.notified() => {}
+                _ = timeout(tick_fallback, self.outbox.ticked()) => self.flush_moves(&mut sink).await?,
+                _ = heartbeat.tick() => {
+                    for (request_id, command) in self.requests.expired(self.config.ack_timeout()) {
+                        warn!("Game server did not ack {} {} in time", command, request_id);
+                        let result = Err("timed out".to_string());
+                        let _ = events.send(LinkEvent::CommandResult { request_id, command, result }).await;
+                    }
+                    if !heartbeat_enabled {
+                        continue;
+                    }
+                    let silent_for = self.silent_for();
+                    if silent_for > self.config.heartbeat_timeout() {
+                        return Err(format!("no heartbeat for {:?}", silent_for));
+                    }
+                    seq += 1;
+                    let ping = PingData { seq, sent_at_ms: self.epoch.elapsed().as_millis() as u64 };
+                    self.write(&mut sink, &GameServerMessage::Ping(ping)).await?;
+                }
+            }
+        }
+    }
+
+    /// Writes the latest move of every player, as a single `players`/`move` when batching.
+    async fn flush_moves(&self, sink: &mut SplitSink<WsStream, Message>) -> Result<(), String> {
+        let moves = self.outbox.take_coalesced();
+        if moves.is_empty() {
+            return Ok(());
+        }
+        if !self.config.batch_moves {
+            for message in &moves {
+                self.write(sink, message).await?;
+            }
+            return Ok(());
+        }
+        let batch = moves
+            .into_iter()
+            .filter_map(|message| match message {
+                GameServerMessage::PlayerMove { player_id, data } => Some(PlayerMoveData { player_id, data }),
+                _ => None,
+            })
+            .collect();
+        self.write(sink, &GameServerMessage::PlayersMove(batch)).await
+    }
+
+    async fn write(&self, sink: &mut SplitSink<WsStream, Message>, message: &GameServerMessage) -> Result<(), String> {
+        let frame = self.encode(message);
+        self.record(Direction::Out, &frame);
+        let started = Instant::now();
+        let result = match timeout(self.config.write_timeout(), sink.send(frame)).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err("write timed out".to_string()),
+        };
+        match &result {
+            Ok(()) => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.sent(message, started.elapsed());
+                }
+            }
+            Err(e) => {
+                error!("Failed to send {}/{} to the game server: {}", message.namespace(), message.event(), e);
+                if let Some(metrics) = &self.metrics {
+                    metrics.send_failed();
+                }
+            }
+        }
+        result
+    }
+
+    fn command_result(&self, request_id: String, result: Result<(), String>) -> Option<LinkEvent> {
+        match self.requests.resolve(&request_id) {
+            Some(command) => Some(LinkEvent::CommandResult { request_id, command, result }),
+            None => {
+                warn!("Game server answered unknown or timed out request {}", request_id);
+                None
+            }
+        }
+    }
+
+    async fn read_loop(
+        &self,
+        mut stream: SplitStream<WsStream>,
+        time_requests: mpsc::Sender<(TimeRequestData, u64)>,
+        events: &mpsc::Sender<LinkEvent>,
+    ) -> Result<(), String> {
+        while let Some(frame) = stream.next().await {
+            let frame = frame.map_err(|e| e.to_string())?;
+            let received_ms = server_time_ms();
+            // any frame, including websocket pongs, proves the game server is alive
+            self.mark_seen();
+            if let Message::Close(close) = frame {
+                debug!("Game server close frame: {:?}", close);
+                return Ok(());
+            }
+            self.record(Direction::In, &frame);
+            let message = self.decode(frame);
+            if let Some(message) = &message {
+                if let Some(metrics) = &self.metrics {
+                    metrics.received(message);
+                }
+            }
+            let event = match message {
+                Some(GameServerMessage::Welcome(welcome)) => {
+                    self.accept_welcome(welcome.encoding);
+                    continue;
+                }
+                Some(GameServerMessage::Pong(pong)) => {
+                    self.accept_pong(pong);
+                    continue;
+                }
+                Some(GameServerMessage::TimeRequest(request)) => {
+                    if time_requests.try_send((request, received_ms)).is_err() {
+                        warn!("Game server sends clock sync requests faster than they are answered, dropping one");
+                    }
+                    continue;
+                }
+                Some(GameServerMessage::Ack(ack)) => match self.command_result(ack.request_id, Ok(())) {
+                    Some(event) => event,
+                    None => continue,
+                },
+                Some(GameServerMessage::Nack(nack)) => match self.command_result(nack.request_id, Err(nack.reason)) {
+                    Some(event) => event,
+                    None => continue,
+                },
+                Some(message) => LinkEvent::Message(message),
+                None => continue,
+            };
+            if events.send(event).await.is_err() {
+                return Ok(()); // the plugin is gone
+            }
+        }
+        Ok(())
+    }
+}
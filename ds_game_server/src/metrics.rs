@@ -0,0 +1,210 @@
+use prometheus::{
+    exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info};
+
+use crate::protocol::GameServerMessage;
+
+/// Prometheus metrics of the game server links, in a registry of their own served
+/// on `[metrics] listen`.
+pub struct Metrics {
+    registry: Registry,
+    messages_received: IntCounterVec,
+    messages_sent: IntCounterVec,
+    send_failures: IntCounterVec,
+    parse_failures: IntCounterVec,
+    reconnects: IntCounterVec,
+    link_up: IntGaugeVec,
+    queue_depth: IntGaugeVec,
+    rtt: HistogramVec,
+    write_duration: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, String> {
+        let registry = Registry::new();
+        let message_labels = &["region", "namespace", "event"];
+        let metrics = Self {
+            messages_received: IntCounterVec::new(
+                Opts::new("ds_game_server_messages_received_total", "Messages received from the game servers"),
+                message_labels,
+            )
+            .map_err(|e| e.to_string())?,
+            messages_sent: IntCounterVec::new(
+                Opts::new("ds_game_server_messages_sent_total", "Messages written to the game servers"),
+                message_labels,
+            )
+            .map_err(|e| e.to_string())?,
+            send_failures: IntCounterVec::new(
+                Opts::new("ds_game_server_send_failures_total", "Writes to a game server that failed or timed out"),
+                &["region"],
+            )
+            .map_err(|e| e.to_string())?,
+            parse_failures: IntCounterVec::new(
+                Opts::new("ds_game_server_parse_failures_total", "Frames from a game server that could not be decoded"),
+                &["region"],
+            )
+            .map_err(|e| e.to_string())?,
+            reconnects: IntCounterVec::new(
+                Opts::new("ds_game_server_reconnects_total", "Links re-established after being lost"),
+                &["region"],
+            )
+            .map_err(|e| e.to_string())?,
+            link_up: IntGaugeVec::new(Opts::new("ds_game_server_link_up", "1 while the link to the game server is up"), &["region"])
+                .map_err(|e| e.to_string())?,
+            queue_depth: IntGaugeVec::new(
+                Opts::new("ds_game_server_queue_depth", "Messages waiting to be written, sampled on every tick"),
+                &["region"],
+            )
+            .map_err(|e| e.to_string())?,
+            rtt: HistogramVec::new(
+                HistogramOpts::new("ds_game_server_link_rtt_seconds", "Round trip time of the link heartbeats")
+                    .buckets(exponential_buckets(0.0005, 2.0, 14).map_err(|e| e.to_string())?),
+                &["region"],
+            )
+            .map_err(|e| e.to_string())?,
+            write_duration: HistogramVec::new(
+                HistogramOpts::new("ds_game_server_write_duration_seconds", "Time spent writing a frame to a game server")
+                    .buckets(exponential_buckets(0.00005, 2.0, 16).map_err(|e| e.to_string())?),
+                &["region"],
+            )
+            .map_err(|e| e.to_string())?,
+            registry,
+        };
+        metrics.register()?;
+        Ok(metrics)
+    }
+
+    fn register(&self) -> Result<(), String> {
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(self.messages_received.clone()),
+            Box::new(self.messages_sent.clone()),
+            Box::new(self.send_failures.clone()),
+            Box::new(self.parse_failures.clone()),
+            Box::new(self.reconnects.clone()),
+            Box::new(self.link_up.clone()),
+            Box::new(self.queue_depth.clone()),
+            Box::new(self.rtt.clone()),
+            Box::new(self.write_duration.clone()),
+        ];
+        for collector in collectors {
+            self.registry.register(collector).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            error!("Failed to encode the metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// Metrics of one link, labelled with the name of its region.
+pub struct LinkMetrics {
+    region: String,
+    metrics: Arc<Metrics>,
+}
+
+impl LinkMetrics {
+    pub fn new(region: &str, metrics: Arc<Metrics>) -> Self {
+        // exported at 0 from the start, so that rates work before the first failure
+        metrics.send_failures.with_label_values(&[region]);
+        metrics.parse_failures.with_label_values(&[region]);
+        metrics.reconnects.with_label_values(&[region]);
+        metrics.link_up.with_label_values(&[region]).set(0);
+        Self {
+            region: region.to_string(),
+            metrics,
+        }
+    }
+
+    pub fn received(&self, message: &GameServerMessage) {
+        let labels = [self.region.as_str(), message.namespace(), message.event()];
+        self.metrics.messages_received.with_label_values(&labels).inc();
+    }
+
+    pub fn sent(&self, message: &GameServerMessage, duration: Duration) {
+        let labels = [self.region.as_str(), message.namespace(), message.event()];
+        self.metrics.messages_sent.with_label_values(&labels).inc();
+        self.metrics.write_duration.with_label_values(&[&self.region]).observe(duration.as_secs_f64());
+    }
+
+    pub fn send_failed(&self) {
+        self.metrics.send_failures.with_label_values(&[&self.region]).inc();
+    }
+
+    pub fn parse_failed(&self) {
+        self.metrics.parse_failures.with_label_values(&[&self.region]).inc();
+    }
+
+    pub fn connected(&self, reconnect: bool) {
+        self.metrics.link_up.with_label_values(&[&self.region]).set(1);
+        if reconnect {
+            self.metrics.reconnects.with_label_values(&[&self.region]).inc();
+        }
+    }
+
+    pub fn disconnected(&self) {
+        self.metrics.link_up.with_label_values(&[&self.region]).set(0);
+    }
+
+    pub fn queue_depth(&self, depth: usize) {
+        self.metrics.queue_depth.with_label_values(&[&self.region]).set(depth as i64);
+    }
+
+    pub fn rtt(&self, rtt: Duration) {
+        self.metrics.rtt.with_label_values(&[&self.region]).observe(rtt.as_secs_f64());
+    }
+}
+
+/// Serves the metrics on `GET /metrics` for Prometheus to scrape.
+pub async fn serve(metrics: Arc<Metrics>, listen: String) {
+    let listener = match TcpListener::bind(&listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("🔧 DsGameServerPlugin: Cannot serve metrics on {}: {}", listen, e);
+            return;
+        }
+    };
+    info!("🔧 DsGameServerPlugin: Serving metrics on http://{}/metrics", listen);
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(answer(stream, Arc::clone(&metrics)));
+            }
+            Err(e) => debug!("Metrics connection failed: {}", e),
+        }
+    }
+}
+
+async fn answer(mut stream: TcpStream, metrics: Arc<Metrics>) {
+    // only the request line matters, scrapes have no body
+    let mut request = [0u8; 1024];
+    let read = match tokio::time::timeout(Duration::from_secs(5), stream.read(&mut request)).await {
+        Ok(Ok(read)) => read,
+        _ => return,
+    };
+    let request = String::from_utf8_lossy(&request[..read]);
+    let response = if request.starts_with("GET /metrics ") || request.starts_with("GET / ") {
+        let body = metrics.render();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        debug!("Failed to answer a metrics scrape: {}", e);
+    }
+    let _ = stream.shutdown().await;
+}
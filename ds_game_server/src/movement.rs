@@ -0,0 +1,297 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::config::MovementConfig;
+use crate::regions::{position_of, Regions};
+
+/// Rounding allowed on the length of a direction and on the angles of a rotation.
+const EPSILON: f64 = 1e-3;
+/// Distance a player may cover on top of `max_speed`, for positions of the same tick
+/// received back to back.
+const POSITION_SLACK: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Vec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Vec3 {
+    fn components(&self) -> [f64; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    fn length(&self) -> f64 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+}
+
+/// A move sent by a client on `movement`/`update_position`, validated by
+/// deserializing it. Unknown fields are ignored, so clients may send more than the
+/// game server reads.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MoveInput {
+    pub seq: Option<u64>,
+    /// Input direction, at most of length 1.
+    pub dir: Option<Vec3>,
+    /// Rotation as angles in radians, each within one turn.
+    pub rot: Option<Vec3>,
+}
+
+impl MoveInput {
+    fn check(&self) -> Result<(), String> {
+        if let Some(dir) = &self.dir {
+            if !dir.components().iter().all(|c| c.is_finite()) {
+                return Err("dir is not finite".to_string());
+            }
+            if dir.length() > 1.0 + EPSILON {
+                return Err(format!("dir of length {:.3} is not normalized", dir.length()));
+            }
+        }
+        if let Some(rot) = &self.rot {
+            if !rot.components().iter().all(|c| c.is_finite()) {
+                return Err("rot is not finite".to_string());
+            }
+            if rot.components().iter().any(|c| c.abs() > TAU + EPSILON) {
+                return Err("rot angle beyond one turn".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViolationKind {
+    /// A move payload that is not a valid `MoveInput`.
+    InvalidInput,
+    /// More moves than `max_inputs_per_second`.
+    RateLimited,
+    /// A reported position further than the player could have gone.
+    PositionJump,
+}
+
+/// Payload of `gameserverplugin`/`movement_violation`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Violation {
+    /// Horizon `PlayerId`, when known.
+    pub player_id: Option<String>,
+    /// Props `Player.uuid`, when the player is spawned.
+    pub player_uuid: Option<String>,
+    pub kind: ViolationKind,
+    pub reason: String,
+    /// False for the following moves of a player who keeps exceeding the rate or
+    /// sending invalid moves: only the first one is reported.
+    #[serde(skip)]
+    pub first: bool,
+}
+
+impl Violation {
+    fn new(player_id: Option<&str>, player_uuid: Option<&str>, kind: ViolationKind, reason: String) -> Self {
+        Self {
+            player_id: player_id.map(str::to_string),
+            player_uuid: player_uuid.map(str::to_string),
+            kind,
+            reason,
+            first: true,
+        }
+    }
+}
+
+/// Token bucket of the moves of a player.
+struct Budget {
+    tokens: f64,
+    refilled: Instant,
+    limited: bool,
+    /// The last move was invalid, and reported.
+    invalid: bool,
+}
+
+/// Anti-cheat checks of the player movement: moves are validated and rate limited
+/// before being forwarded, and the positions sent back by the game servers are
+/// checked against the maximum speed.
+pub struct MovementGuard {
+    config: MovementConfig,
+    /// Keyed by Horizon `PlayerId`.
+    budgets: Mutex<HashMap<String, Budget>>,
+    /// Last reported position of each player and when it was received, keyed by
+    /// props `Player.uuid`.
+    positions: Mutex<HashMap<String, ([f64; 3], Instant)>>,
+}
+
+impl MovementGuard {
+    pub fn new(config: MovementConfig) -> Self {
+        Self {
+            config,
+            budgets: Mutex::new(HashMap::new()),
+            positions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Accepts a move, or returns why it must be dropped. Every move spends a token,
+    /// invalid ones included, so a flood of them is limited like any other.
+    pub fn check_input(&self, player_id: &str, player_uuid: Option<&str>, data: &Value) -> Result<(), Violation> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let rate = self.config.max_inputs_per_second as f64;
+        let mut budgets = self.budgets.lock().unwrap();
+        let budget = budgets.entry(player_id.to_string()).or_insert_with(|| Budget {
+            tokens: rate,
+            refilled: Instant::now(),
+            limited: false,
+            invalid: false,
+        });
+        budget.tokens = (budget.tokens + budget.refilled.elapsed().as_secs_f64() * rate).min(rate);
+        budget.refilled = Instant::now();
+        if budget.tokens < 1.0 {
+            let reason = format!("more than {} moves per second", self.config.max_inputs_per_second);
+            let mut violation = Violation::new(Some(player_id), player_uuid, ViolationKind::RateLimited, reason);
+            violation.first = !budget.limited;
+            budget.limited = true;
+            return Err(violation);
+        }
+        budget.tokens -= 1.0;
+        budget.limited = false;
+
+        let checked = serde_json::from_value::<MoveInput>(data.clone())
+            .map_err(|e| e.to_string())
+            .and_then(|input| input.check());
+        if let Err(reason) = checked {
+            let mut violation = Violation::new(Some(player_id), player_uuid, ViolationKind::InvalidInput, reason);
+            violation.first = !budget.invalid;
+            budget.invalid = true;
+            return Err(violation);
+        }
+        budget.invalid = false;
+        Ok(())
+    }
+
+    /// Checks the players of a `players`/`position` update of region `index` and
+    /// returns those who moved too far.
+    pub fn check_positions(&self, regions: &Regions, index: usize, players: &Value) -> Vec<Violation> {
+        let Some(players) = players.as_array() else {
+            return Vec::new();
+        };
+        if !self.config.enabled {
+            return Vec::new();
+        }
+        let mut violations = Vec::new();
+        let mut positions = self.positions.lock().unwrap();
+        for player in players {
+            let Some(player_uuid) = player["uuid"].as_str() else {
+                continue;
+            };
+            // other instances may still report a player handed off from them
+            if regions.player_region(player_uuid) != index {
+                continue;
+            }
+            let Some(position) = position_of(player) else {
+                continue;
+            };
+            let now = Instant::now();
+            if let Some((previous, at)) = positions.insert(player_uuid.to_string(), (position, now)) {
+                let distance = (0..3).map(|i| (position[i] - previous[i]).powi(2)).sum::<f64>().sqrt();
+                let allowed = self.config.max_speed * now.duration_since(at).as_secs_f64() + POSITION_SLACK;
+                if distance > allowed {
+                    let reason = format!("moved {:.2} m in {:?}, at most {:.2} m", distance, now.duration_since(at), allowed);
+                    let player_id = player["internal_uuid"].as_str();
+                    violations.push(Violation::new(player_id, Some(player_uuid), ViolationKind::PositionJump, reason));
+                }
+            }
+        }
+        violations
+    }
+
+    pub fn forget_player(&self, player_id: &str, player_uuid: Option<&str>) {
+        self.budgets.lock().unwrap().remove(player_id);
+        if let Some(player_uuid) = player_uuid {
+            self.positions.lock().unwrap().remove(player_uuid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameServerConfig;
+    use serde_json::json;
+
+    fn guard(max_inputs_per_second: u32) -> MovementGuard {
+        MovementGuard::new(MovementConfig {
+            max_inputs_per_second,
+            ..MovementConfig::default()
+        })
+    }
+
+    fn walk() -> Value {
+        json!({ "seq": 1, "dir": { "x": 0.6, "y": 0.0, "z": 0.8 }, "rot": { "x": 0.0, "y": 3.0, "z": 0.0 } })
+    }
+
+    #[test]
+    fn invalid_moves_are_reported_once_until_a_valid_one() {
+        let guard = guard(60);
+        assert!(guard.check_input("p1", Some("alice"), &walk()).is_ok());
+
+        let teleport = json!({ "dir": { "x": 50.0, "y": 0.0, "z": 0.0 } });
+        let violation = guard.check_input("p1", Some("alice"), &teleport).unwrap_err();
+        assert_eq!(violation.kind, ViolationKind::InvalidInput);
+        assert!(violation.first);
+        let violation = guard.check_input("p1", Some("alice"), &json!({ "rot": "up" })).unwrap_err();
+        assert_eq!(violation.kind, ViolationKind::InvalidInput);
+        assert!(!violation.first);
+
+        assert!(guard.check_input("p1", Some("alice"), &walk()).is_ok());
+        assert!(guard.check_input("p1", Some("alice"), &teleport).unwrap_err().first);
+    }
+
+    #[test]
+    fn invalid_moves_spend_the_rate_limit() {
+        let guard = guard(3);
+        let invalid = json!({ "rot": { "x": 10.0, "y": 0.0, "z": 0.0 } });
+        for _ in 0..3 {
+            assert_eq!(guard.check_input("p1", None, &invalid).unwrap_err().kind, ViolationKind::InvalidInput);
+        }
+        let violation = guard.check_input("p1", None, &walk()).unwrap_err();
+        assert_eq!(violation.kind, ViolationKind::RateLimited);
+        assert!(violation.first);
+        let violation = guard.check_input("p1", None, &walk()).unwrap_err();
+        assert_eq!(violation.kind, ViolationKind::RateLimited);
+        assert!(!violation.first);
+        // each player has its own budget
+        assert!(guard.check_input("p2", None, &walk()).is_ok());
+    }
+
+    #[test]
+    fn disabled_guard_accepts_everything() {
+        let guard = MovementGuard::new(MovementConfig {
+            enabled: false,
+            max_inputs_per_second: 1,
+            ..MovementConfig::default()
+        });
+        for _ in 0..5 {
+            assert!(guard.check_input("p1", None, &json!({ "dir": "nowhere" })).is_ok());
+        }
+    }
+
+    #[test]
+    fn positions_beyond_the_maximum_speed_are_flagged() {
+        let guard = guard(60);
+        let regions = Regions::new(&GameServerConfig::default(), &[], None, None);
+        let at = |x: f64| json!([{ "uuid": "alice", "internal_uuid": "p1", "position": { "x": x, "y": 0.0, "z": 0.0 } }]);
+
+        assert!(guard.check_positions(&regions, 0, &at(0.0)).is_empty());
+        assert!(guard.check_positions(&regions, 0, &at(0.5)).is_empty());
+        let violations = guard.check_positions(&regions, 0, &at(100.0));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, ViolationKind::PositionJump);
+        assert_eq!(violations[0].player_id.as_deref(), Some("p1"));
+        // reports of an instance not owning the player are ignored
+        assert!(guard.check_positions(&regions, 1, &at(500.0)).is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::protocol::GameServerMessage;
+
+/// How a message waiting for the writer task is treated under load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryPolicy {
+    /// Delivered once, in order, refused only when the outbox is full (spawns,
+    /// snapshots, handshakes).
+    Reliable,
+    /// Only the latest message with the same key is worth sending: a newer one
+    /// replaces the pending one (player moves, keyed by player).
+    Coalesce(String),
+}
+
+impl DeliveryPolicy {
+    pub fn of(message: &GameServerMessage) -> Self {
+        match message {
+            GameServerMessage::PlayerMove { player_id, .. } => DeliveryPolicy::Coalesce(player_id.clone()),
+            _ => DeliveryPolicy::Reliable,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Lanes {
+    /// Reliable messages with the id [`Outbox::written`] confirms them with.
+    reliable: VecDeque<(u64, GameServerMessage)>,
+    next_id: u64,
+    /// Set once the reliable lane went past the warning threshold, until it drains.
+    warned: bool,
+    coalesced: HashMap<String, GameServerMessage>,
+    /// Keys of `coalesced` in arrival order, so moves go out fairly.
+    coalesced_order: VecDeque<String>,
+}
+
+/// Messages waiting for the writer task.
+///
+/// Producers are the synchronous event handlers, the consumer is the single writer
+/// task of the current connection. Coalesced messages are bounded by the number of
+/// keys; reliable ones by `capacity`, with a warning once three quarters of it are used.
+pub struct Outbox {
+    lanes: Mutex<Lanes>,
+    notify: Notify,
+    tick: Notify,
+    capacity: usize,
+}
+
+impl Outbox {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lanes: Mutex::new(Lanes::default()),
+            notify: Notify::new(),
+            tick: Notify::new(),
+            capacity,
+        }
+    }
+
+    /// Queues a message, or gives it back if the reliable lane is full.
+    pub fn push(&self, message: GameServerMessage) -> Result<(), GameServerMessage> {
+        {
+            let mut lanes = self.lanes.lock().unwrap();
+            match DeliveryPolicy::of(&message) {
+                DeliveryPolicy::Reliable => {
+                    let queued = lanes.reliable.len();
+                    if queued >= self.capacity {
+                        return Err(message);
+                    }
+                    if queued >= self.capacity * 3 / 4 && !lanes.warned {
+                        warn!("Game server outbox holds {} reliable messages out of {}, the link is not keeping up", queued, self.capacity);
+                        lanes.warned = true;
+                    }
+                    let id = lanes.next_id;
+                    lanes.next_id += 1;
+                    lanes.reliable.push_back((id, message));
+                }
+                DeliveryPolicy::Coalesce(key) => {
+                    if lanes.coalesced.insert(key.clone(), message).is_none() {
+                        lanes.coalesced_order.push_back(key);
+                    }
+                    // coalesced messages wait for the next tick
+                    return Ok(());
+                }
+            }
+        }
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Next reliable message to write and its id.
+    ///
+    /// It stays queued until [`Outbox::written`] is called, so a connection dropped in
+    /// the middle of the write sends it again on the next one.
+    pub fn next(&self) -> Option<(u64, GameServerMessage)> {
+        self.lanes.lock().unwrap().reliable.front().cloned()
+    }
+
+    /// Puts `sync` in front of the reliable lane in place of the waiting messages it
+    /// supersedes, which are returned. The others stay queued after it.
+    pub fn resync(&self, sync: GameServerMessage, superseded: impl Fn(&GameServerMessage) -> bool) -> Vec<GameServerMessage> {
+        let dropped = {
+            let mut lanes = self.lanes.lock().unwrap();
+            let (dropped, kept): (VecDeque<_>, VecDeque<_>) =
+                lanes.reliable.drain(..).partition(|(_, message)| superseded(message));
+            let id = lanes.next_id;
+            lanes.next_id += 1;
+            lanes.reliable = kept;
+            lanes.reliable.push_front((id, sync));
+            dropped.into_iter().map(|(_, message)| message).collect()
+        };
+        self.notify.notify_one();
+        dropped
+    }
+
+    /// Takes every pending coalesced message, in arrival order. They are not kept on
+    /// failure, a newer one will follow anyway.
+    pub fn take_coalesced(&self) -> Vec<GameServerMessage> {
+        let mut lanes = self.lanes.lock().unwrap();
+        let lanes = &mut *lanes;
+        lanes
+            .coalesced_order
+            .drain(..)
+            .filter_map(|key| lanes.coalesced.remove(&key))
+            .collect()
+    }
+
+    /// Drops the pending coalesced message with this key, if any.
+    pub fn discard(&self, key: &str) {
+        let mut lanes = self.lanes.lock().unwrap();
+        if lanes.coalesced.remove(key).is_some() {
+            lanes.coalesced_order.retain(|k| k != key);
+        }
+    }
+
+    /// Confirms that the message returned by [`Outbox::next`] with this id was written.
+    /// It may already be gone, or no longer in front, if a resync replaced it meanwhile.
+    pub fn written(&self, id: u64) {
+        let mut lanes = self.lanes.lock().unwrap();
+        if let Some(position) = lanes.reliable.iter().position(|(queued, _)| *queued == id) {
+            lanes.reliable.remove(position);
+        }
+        if lanes.reliable.len() <= self.capacity / 2 {
+            lanes.warned = false;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let lanes = self.lanes.lock().unwrap();
+        lanes.reliable.len() + lanes.coalesced.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Waits until a reliable message is pushed. A push made before the call is not missed.
+    pub async fn notified(&self) {
+        self.notify.notified().await
+    }
+
+    /// Marks the start of a server tick: coalesced messages are due.
+    pub fn tick(&self) {
+        self.tick.notify_one();
+    }
+
+    /// Waits for the next [`Outbox::tick`].
+    pub async fn ticked(&self) {
+        self.tick.notified().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{PingData, PlayerRemoveData, SyncWorldData};
+    use serde_json::json;
+
+    fn remove(player_uuid: &str) -> GameServerMessage {
+        GameServerMessage::PlayerRemove(PlayerRemoveData {
+            player_uuid: player_uuid.to_string(),
+        })
+    }
+
+    fn step(player_id: &str, seq: u64) -> GameServerMessage {
+        GameServerMessage::PlayerMove {
+            player_id: player_id.to_string(),
+            data: json!({ "seq": seq }),
+        }
+    }
+
+    fn sync() -> GameServerMessage {
+        GameServerMessage::SyncWorld(SyncWorldData {
+            planets: Vec::new(),
+            players: Vec::new(),
+            boxes50cm: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn reliable_messages_stay_queued_until_written() {
+        let outbox = Outbox::new(8);
+        outbox.push(remove("a")).unwrap();
+        outbox.push(remove("b")).unwrap();
+
+        let (id, message) = outbox.next().unwrap();
+        assert_eq!(message, remove("a"));
+        // a connection lost before the write gets it again
+        assert_eq!(outbox.next().unwrap(), (id, remove("a")));
+        outbox.written(id);
+        assert_eq!(outbox.next().unwrap().1, remove("b"));
+    }
+
+    #[test]
+    fn full_reliable_lane_refuses_messages() {
+        let outbox = Outbox::new(2);
+        outbox.push(remove("a")).unwrap();
+        outbox.push(remove("b")).unwrap();
+        assert_eq!(outbox.push(remove("c")), Err(remove("c")));
+        // moves are coalesced, not bounded by the capacity
+        outbox.push(step("p1", 1)).unwrap();
+
+        outbox.written(outbox.next().unwrap().0);
+        outbox.push(remove("c")).unwrap();
+    }
+
+    #[test]
+    fn resync_goes_first_in_place_of_what_it_supersedes() {
+        let outbox = Outbox::new(8);
+        outbox.push(remove("a")).unwrap();
+        outbox.push(GameServerMessage::Ping(PingData { seq: 1, sent_at_ms: 0 })).unwrap();
+        outbox.push(remove("b")).unwrap();
+
+        let dropped = outbox.resync(sync(), |message| matches!(message, GameServerMessage::PlayerRemove(_)));
+        assert_eq!(dropped, vec![remove("a"), remove("b")]);
+        assert_eq!(outbox.next().unwrap().1, sync());
+        outbox.written(outbox.next().unwrap().0);
+        assert!(matches!(outbox.next().unwrap().1, GameServerMessage::Ping(_)));
+    }
+
+    #[test]
+    fn written_after_a_resync_removes_the_message_written() {
+        let outbox = Outbox::new(8);
+        outbox.push(remove("a")).unwrap();
+        outbox.push(remove("b")).unwrap();
+        // the writer took the first message, then a resync came in front of it
+        let (id, _) = outbox.next().unwrap();
+        outbox.resync(sync(), |_| false);
+        outbox.written(id);
+
+        assert_eq!(outbox.next().unwrap().1, sync());
+        outbox.written(outbox.next().unwrap().0);
+        assert_eq!(outbox.next().unwrap().1, remove("b"));
+    }
+
+    #[test]
+    fn latest_move_of_each_player_is_kept_in_arrival_order() {
+        let outbox = Outbox::new(8);
+        outbox.push(step("p1", 1)).unwrap();
+        outbox.push(step("p2", 1)).unwrap();
+        outbox.push(step("p1", 2)).unwrap();
+        outbox.push(step("p3", 1)).unwrap();
+        outbox.discard("p3");
+
+        assert_eq!(outbox.take_coalesced(), vec![step("p1", 2), step("p2", 1)]);
+        assert!(outbox.is_empty());
+    }
+}
@@ -0,0 +1,603 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::actions::PlayerAction;
+
+/// Version of the Horizon ↔ game server protocol spoken by this plugin.
+/// Frames without a version are from game servers predating the field and
+/// are read as version 1.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Encoding of the frames exchanged on the link.
+///
+/// Control messages are always JSON text. Once the game server accepted MessagePack
+/// in its `server`/`welcome`, high-frequency messages are sent as binary frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WireFormat {
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl FromStr for WireFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(WireFormat::Json),
+            "msgpack" => Ok(WireFormat::MessagePack),
+            _ => Err(format!("unknown wire format {}", s)),
+        }
+    }
+}
+
+/// Errors raised while decoding a frame from the game server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolError {
+    /// The frame is not a valid envelope (bad JSON, missing namespace/event...).
+    Malformed(String),
+    /// The frame was produced by a newer protocol than this plugin understands.
+    UnsupportedVersion(u16),
+    /// No message is defined for this namespace/event pair.
+    UnknownMessage { namespace: String, event: String },
+    /// The message is known but its data does not match the expected shape.
+    InvalidPayload { namespace: String, event: String, reason: String },
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Malformed(reason) => write!(f, "malformed frame: {}", reason),
+            ProtocolError::UnsupportedVersion(version) => {
+                write!(f, "unsupported protocol version {} (expected <= {})", version, PROTOCOL_VERSION)
+            }
+            ProtocolError::UnknownMessage { namespace, event } => {
+                write!(f, "unknown message {}/{}", namespace, event)
+            }
+            ProtocolError::InvalidPayload { namespace, event, reason } => {
+                write!(f, "invalid payload for {}/{}: {}", namespace, event, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// Wire envelope shared by every message, see the tables in the README.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Frame {
+    #[serde(default = "legacy_version")]
+    pub version: u16,
+    pub namespace: String,
+    pub event: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_id: Option<String>,
+    #[serde(default)]
+    pub data: Value,
+}
+
+fn legacy_version() -> u16 {
+    1
+}
+
+/// Opens the handshake of a connection when a shared secret is set
+/// (`server`/`auth_challenge`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthChallengeData {
+    pub nonce: String,
+}
+
+/// The game server proves it knows the secret and challenges Horizon in turn
+/// (`server`/`auth_response`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthResponseData {
+    pub nonce: String,
+    /// HMAC-SHA256 over the Horizon nonce then the game server one.
+    pub mac: String,
+}
+
+/// Horizon proves it knows the secret (`server`/`auth_proof`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthProofData {
+    /// HMAC-SHA256 over the game server nonce then the Horizon one.
+    pub mac: String,
+}
+
+/// Sent by Horizon on every connection (`server`/`hello`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HelloData {
+    /// Encodings Horizon accepts, preferred first.
+    pub encodings: Vec<WireFormat>,
+}
+
+/// Answer of the game server to `hello` (`server`/`welcome`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WelcomeData {
+    pub encoding: WireFormat,
+}
+
+/// Heartbeat sent by Horizon (`server`/`ping`), echoed as is by the game server
+/// (`server`/`pong`) so the round trip time can be measured.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PingData {
+    pub seq: u64,
+    /// Milliseconds on the Horizon side clock, only meaningful to Horizon.
+    pub sent_at_ms: u64,
+}
+
+/// Clock synchronization asked by the game server (`server`/`time_request`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeRequestData {
+    /// Game server clock when the request was sent.
+    pub t0: u64,
+}
+
+/// Horizon clock shared with the game servers and the clients, in milliseconds since
+/// the Unix epoch.
+pub fn server_time_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Answer of Horizon to `server`/`time_request` (`server`/`time_response`), with its
+/// clock in milliseconds since the Unix epoch when the request was received (`t1`)
+/// and when the answer was sent (`t2`), NTP style.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeResponseData {
+    pub t0: u64,
+    pub t1: u64,
+    pub t2: u64,
+}
+
+/// Initial props or a newly connected player (`server`/`add_props`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddPropsData {
+    #[serde(default)]
+    pub planets: Vec<Value>,
+    pub player: Value,
+    /// Correlation id answered by a `server`/`ack` or `server`/`nack`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// A prop spawned by a player (`server`/`add_prop`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddPropData {
+    pub box50cm: Value,
+    #[serde(default)]
+    pub player_uuid: String,
+    /// Correlation id answered by a `server`/`ack` or `server`/`nack`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// The game server executed the command with this id (`server`/`ack`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AckData {
+    pub request_id: String,
+}
+
+/// The game server failed to execute the command with this id (`server`/`nack`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NackData {
+    pub request_id: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// A player left Horizon, its character must be despawned (`player`/`remove`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerRemoveData {
+    /// `uuid` of the player as sent in `add_props`.
+    pub player_uuid: String,
+}
+
+/// An action of a player simulated by the game server (`player`/`action`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerActionData {
+    /// `uuid` of the player as sent in `add_props`.
+    pub player_uuid: String,
+    #[serde(flatten)]
+    pub action: PlayerAction,
+}
+
+/// Full world snapshot replayed after a reconnect (`server`/`sync_world`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncWorldData {
+    #[serde(default)]
+    pub planets: Vec<Value>,
+    #[serde(default)]
+    pub players: Vec<Value>,
+    #[serde(default)]
+    pub boxes50cm: Vec<Value>,
+}
+
+impl SyncWorldData {
+    fn has(items: &[Value], uuid: &str) -> bool {
+        items.iter().any(|item| item["uuid"] == uuid)
+    }
+
+    /// Whether a command still waiting to be sent is already accounted for by this
+    /// snapshot: spawns of what it contains, removals of what it no longer contains,
+    /// earlier syncs and handoffs, aborted when the link went down.
+    pub fn supersedes(&self, message: &GameServerMessage) -> bool {
+        match message {
+            GameServerMessage::AddProps(data) => data.player["uuid"].as_str().is_some_and(|uuid| Self::has(&self.players, uuid)),
+            GameServerMessage::AddProp(data) => data.box50cm["uuid"].as_str().is_some_and(|uuid| Self::has(&self.boxes50cm, uuid)),
+            GameServerMessage::PlayerRemove(data) => !Self::has(&self.players, &data.player_uuid),
+            GameServerMessage::SyncWorld(_)
+            | GameServerMessage::HandoffFreeze(_)
+            | GameServerMessage::HandoffPrepare(_)
+            | GameServerMessage::HandoffCommit(_)
+            | GameServerMessage::HandoffAbort(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A step of the handoff of a player between two instances (`handoff`/`freeze`,
+/// `handoff`/`ready`, `handoff`/`commit` and `handoff`/`abort`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HandoffData {
+    pub handoff_id: String,
+    pub player_uuid: String,
+}
+
+/// Exact state of a frozen player, sent by the instance it leaves (`handoff`/`state`)
+/// and forwarded to the instance it joins (`handoff`/`prepare`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HandoffStateData {
+    pub handoff_id: String,
+    /// The player as in `add_props`, plus its `velocity`.
+    pub player: Value,
+}
+
+/// Latest movement input of one player, as batched in `players`/`move`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerMoveData {
+    pub player_id: String,
+    pub data: Value,
+}
+
+/// Every message exchanged between Horizon and the game server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Frame", into = "Frame")]
+pub enum GameServerMessage {
+    // Horizon -> game server
+    AuthChallenge(AuthChallengeData),
+    AuthProof(AuthProofData),
+    Hello(HelloData),
+    Ping(PingData),
+    TimeResponse(TimeResponseData),
+    AddProps(AddPropsData),
+    AddProp(AddPropData),
+    SyncWorld(SyncWorldData),
+    /// Raw movement input of a player (`player`/`move`).
+    PlayerMove { player_id: String, data: Value },
+    PlayerRemove(PlayerRemoveData),
+    PlayerAction(PlayerActionData),
+    /// Latest input of every player that moved during the tick (`players`/`move`).
+    PlayersMove(Vec<PlayerMoveData>),
+    /// Stop simulating a player leaving the instance and report its state.
+    HandoffFreeze(HandoffData),
+    /// Spawn a player joining the instance, hidden until the commit.
+    HandoffPrepare(HandoffStateData),
+    /// The handoff is done: the old instance removes the player, the new one shows it.
+    HandoffCommit(HandoffData),
+    /// The handoff failed: the old instance resumes the player, the new one drops it.
+    HandoffAbort(HandoffData),
+
+    // game server -> Horizon
+    AuthResponse(AuthResponseData),
+    Welcome(WelcomeData),
+    Pong(PingData),
+    TimeRequest(TimeRequestData),
+    Ack(AckData),
+    Nack(NackData),
+    /// Positions of the simulated players (`players`/`position`).
+    PlayersPosition(Value),
+    /// Positions of the simulated props (`props`/`position`).
+    PropsPosition(Value),
+    HandoffState(HandoffStateData),
+    /// The joining instance spawned the player of `handoff`/`prepare`.
+    HandoffReady(HandoffData),
+}
+
+impl GameServerMessage {
+    pub fn namespace(&self) -> &'static str {
+        match self {
+            GameServerMessage::AuthChallenge(_)
+            | GameServerMessage::AuthResponse(_)
+            | GameServerMessage::AuthProof(_)
+            | GameServerMessage::Hello(_)
+            | GameServerMessage::Welcome(_)
+            | GameServerMessage::Ping(_)
+            | GameServerMessage::Pong(_)
+            | GameServerMessage::TimeRequest(_)
+            | GameServerMessage::TimeResponse(_)
+            | GameServerMessage::Ack(_)
+            | GameServerMessage::Nack(_)
+            | GameServerMessage::AddProps(_)
+            | GameServerMessage::AddProp(_)
+            | GameServerMessage::SyncWorld(_) => "server",
+            GameServerMessage::PlayerMove { .. }
+            | GameServerMessage::PlayerRemove(_)
+            | GameServerMessage::PlayerAction(_) => "player",
+            GameServerMessage::PlayersMove(_) | GameServerMessage::PlayersPosition(_) => "players",
+            GameServerMessage::PropsPosition(_) => "props",
+            GameServerMessage::HandoffFreeze(_)
+            | GameServerMessage::HandoffState(_)
+            | GameServerMessage::HandoffPrepare(_)
+            | GameServerMessage::HandoffReady(_)
+            | GameServerMessage::HandoffCommit(_)
+            | GameServerMessage::HandoffAbort(_) => "handoff",
+        }
+    }
+
+    pub fn event(&self) -> &'static str {
+        match self {
+            GameServerMessage::AuthChallenge(_) => "auth_challenge",
+            GameServerMessage::AuthResponse(_) => "auth_response",
+            GameServerMessage::AuthProof(_) => "auth_proof",
+            GameServerMessage::Hello(_) => "hello",
+            GameServerMessage::Welcome(_) => "welcome",
+            GameServerMessage::Ping(_) => "ping",
+            GameServerMessage::Pong(_) => "pong",
+            GameServerMessage::TimeRequest(_) => "time_request",
+            GameServerMessage::TimeResponse(_) => "time_response",
+            GameServerMessage::Ack(_) => "ack",
+            GameServerMessage::Nack(_) => "nack",
+            GameServerMessage::AddProps(_) => "add_props",
+            GameServerMessage::AddProp(_) => "add_prop",
+            GameServerMessage::SyncWorld(_) => "sync_world",
+            GameServerMessage::PlayerMove { .. } | GameServerMessage::PlayersMove(_) => "move",
+            GameServerMessage::PlayerRemove(_) => "remove",
+            GameServerMessage::PlayerAction(_) => "action",
+            GameServerMessage::PlayersPosition(_) | GameServerMessage::PropsPosition(_) => "position",
+            GameServerMessage::HandoffFreeze(_) => "freeze",
+            GameServerMessage::HandoffState(_) => "state",
+            GameServerMessage::HandoffPrepare(_) => "prepare",
+            GameServerMessage::HandoffReady(_) => "ready",
+            GameServerMessage::HandoffCommit(_) => "commit",
+            GameServerMessage::HandoffAbort(_) => "abort",
+        }
+    }
+
+    /// Messages sent many times per second, worth a compact binary encoding.
+    pub fn is_high_frequency(&self) -> bool {
+        matches!(
+            self,
+            GameServerMessage::PlayerMove { .. }
+                | GameServerMessage::PlayersMove(_)
+                | GameServerMessage::PlayersPosition(_)
+                | GameServerMessage::PropsPosition(_)
+        )
+    }
+
+    /// Correlation id of a command expecting an ack, if it has one.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            GameServerMessage::AddProps(data) => data.request_id.as_deref(),
+            GameServerMessage::AddProp(data) => data.request_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // every payload is made of JSON values and strings, it cannot fail
+        serde_json::to_string(self).expect("game server message is always serializable")
+    }
+
+    /// Decodes a JSON frame, reporting precisely why it was rejected.
+    pub fn from_json(text: &str) -> Result<Self, ProtocolError> {
+        let frame: Frame = serde_json::from_str(text).map_err(|e| ProtocolError::Malformed(e.to_string()))?;
+        GameServerMessage::try_from(frame)
+    }
+
+    pub fn to_msgpack(&self) -> Vec<u8> {
+        // field names are kept so the frame decodes into the same envelope as JSON
+        rmp_serde::to_vec_named(self).expect("game server message is always serializable")
+    }
+
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let frame: Frame = rmp_serde::from_slice(bytes).map_err(|e| ProtocolError::Malformed(e.to_string()))?;
+        GameServerMessage::try_from(frame)
+    }
+
+    /// Decodes a binary frame: MessagePack, or UTF-8 JSON from older game servers.
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        if bytes.first() == Some(&b'{') {
+            let text = std::str::from_utf8(bytes).map_err(|e| ProtocolError::Malformed(e.to_string()))?;
+            return GameServerMessage::from_json(text);
+        }
+        GameServerMessage::from_msgpack(bytes)
+    }
+}
+
+fn payload<T: serde::de::DeserializeOwned>(frame: &Frame) -> Result<T, ProtocolError> {
+    serde_json::from_value(frame.data.clone()).map_err(|e| ProtocolError::InvalidPayload {
+        namespace: frame.namespace.clone(),
+        event: frame.event.clone(),
+        reason: e.to_string(),
+    })
+}
+
+impl TryFrom<Frame> for GameServerMessage {
+    type Error = ProtocolError;
+
+    fn try_from(frame: Frame) -> Result<Self, Self::Error> {
+        if frame.version > PROTOCOL_VERSION {
+            return Err(ProtocolError::UnsupportedVersion(frame.version));
+        }
+        match (frame.namespace.as_str(), frame.event.as_str()) {
+            ("server", "auth_challenge") => Ok(GameServerMessage::AuthChallenge(payload(&frame)?)),
+            ("server", "auth_response") => Ok(GameServerMessage::AuthResponse(payload(&frame)?)),
+            ("server", "auth_proof") => Ok(GameServerMessage::AuthProof(payload(&frame)?)),
+            ("server", "hello") => Ok(GameServerMessage::Hello(payload(&frame)?)),
+            ("server", "welcome") => Ok(GameServerMessage::Welcome(payload(&frame)?)),
+            ("server", "ping") => Ok(GameServerMessage::Ping(payload(&frame)?)),
+            ("server", "pong") => Ok(GameServerMessage::Pong(payload(&frame)?)),
+            ("server", "time_request") => Ok(GameServerMessage::TimeRequest(payload(&frame)?)),
+            ("server", "time_response") => Ok(GameServerMessage::TimeResponse(payload(&frame)?)),
+            ("server", "ack") => Ok(GameServerMessage::Ack(payload(&frame)?)),
+            ("server", "nack") => Ok(GameServerMessage::Nack(payload(&frame)?)),
+            ("server", "add_props") => Ok(GameServerMessage::AddProps(payload(&frame)?)),
+            ("server", "add_prop") => Ok(GameServerMessage::AddProp(payload(&frame)?)),
+            ("server", "sync_world") => Ok(GameServerMessage::SyncWorld(payload(&frame)?)),
+            ("player", "move") => match frame.player_id {
+                Some(player_id) => Ok(GameServerMessage::PlayerMove { player_id, data: frame.data }),
+                None => Err(ProtocolError::InvalidPayload {
+                    namespace: frame.namespace,
+                    event: frame.event,
+                    reason: "missing player_id".to_string(),
+                }),
+            },
+            ("player", "remove") => Ok(GameServerMessage::PlayerRemove(payload(&frame)?)),
+            ("player", "action") => Ok(GameServerMessage::PlayerAction(payload(&frame)?)),
+            ("players", "move") => Ok(GameServerMessage::PlayersMove(payload(&frame)?)),
+            ("players", "position") => Ok(GameServerMessage::PlayersPosition(frame.data)),
+            ("props", "position") => Ok(GameServerMessage::PropsPosition(frame.data)),
+            ("handoff", "freeze") => Ok(GameServerMessage::HandoffFreeze(payload(&frame)?)),
+            ("handoff", "state") => Ok(GameServerMessage::HandoffState(payload(&frame)?)),
+            ("handoff", "prepare") => Ok(GameServerMessage::HandoffPrepare(payload(&frame)?)),
+            ("handoff", "ready") => Ok(GameServerMessage::HandoffReady(payload(&frame)?)),
+            ("handoff", "commit") => Ok(GameServerMessage::HandoffCommit(payload(&frame)?)),
+            ("handoff", "abort") => Ok(GameServerMessage::HandoffAbort(payload(&frame)?)),
+            _ => Err(ProtocolError::UnknownMessage {
+                namespace: frame.namespace,
+                event: frame.event,
+            }),
+        }
+    }
+}
+
+impl From<GameServerMessage> for Frame {
+    fn from(message: GameServerMessage) -> Self {
+        let namespace = message.namespace().to_string();
+        let event = message.event().to_string();
+        let (player_id, data) = match message {
+            GameServerMessage::AuthChallenge(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::AuthResponse(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::AuthProof(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::Hello(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::Welcome(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::Ping(data) | GameServerMessage::Pong(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::TimeRequest(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::TimeResponse(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::Ack(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::Nack(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::AddProps(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::AddProp(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::SyncWorld(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::PlayerMove { player_id, data } => (Some(player_id), Ok(data)),
+            GameServerMessage::PlayerRemove(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::PlayerAction(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::PlayersMove(moves) => (None, serde_json::to_value(moves)),
+            GameServerMessage::PlayersPosition(data) | GameServerMessage::PropsPosition(data) => (None, Ok(data)),
+            GameServerMessage::HandoffFreeze(data)
+            | GameServerMessage::HandoffReady(data)
+            | GameServerMessage::HandoffCommit(data)
+            | GameServerMessage::HandoffAbort(data) => (None, serde_json::to_value(data)),
+            GameServerMessage::HandoffState(data) | GameServerMessage::HandoffPrepare(data) => (None, serde_json::to_value(data)),
+        };
+        Frame {
+            version: PROTOCOL_VERSION,
+            namespace,
+            event,
+            player_id,
+            data: data.unwrap_or(Value::Null),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn frames_without_version_are_version_1() {
+        let message = GameServerMessage::from_json(r#"{"namespace":"player","event":"remove","data":{"player_uuid":"alice"}}"#);
+        assert_eq!(
+            message,
+            Ok(GameServerMessage::PlayerRemove(PlayerRemoveData {
+                player_uuid: "alice".to_string()
+            }))
+        );
+        let frame: Frame = GameServerMessage::Ping(PingData { seq: 1, sent_at_ms: 2 }).into();
+        assert_eq!(frame.version, PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn rejected_frames_say_why() {
+        let newer = json!({ "version": PROTOCOL_VERSION + 1, "namespace": "server", "event": "ping", "data": {} });
+        assert_eq!(
+            GameServerMessage::from_json(&newer.to_string()),
+            Err(ProtocolError::UnsupportedVersion(PROTOCOL_VERSION + 1))
+        );
+        assert_eq!(
+            GameServerMessage::from_json(r#"{"namespace":"server","event":"teleport","data":{}}"#),
+            Err(ProtocolError::UnknownMessage {
+                namespace: "server".to_string(),
+                event: "teleport".to_string()
+            })
+        );
+        let bad_payload = GameServerMessage::from_json(r#"{"namespace":"server","event":"ping","data":{"seq":"one"}}"#);
+        assert!(matches!(bad_payload, Err(ProtocolError::InvalidPayload { .. })), "{:?}", bad_payload);
+        let move_without_player = GameServerMessage::from_json(r#"{"namespace":"player","event":"move","data":{}}"#);
+        assert!(matches!(move_without_player, Err(ProtocolError::InvalidPayload { .. })), "{:?}", move_without_player);
+        assert!(matches!(GameServerMessage::from_json("{"), Err(ProtocolError::Malformed(_))));
+    }
+
+    #[test]
+    fn messages_roundtrip_in_json_and_msgpack() {
+        let message = GameServerMessage::PlayerMove {
+            player_id: "p1".to_string(),
+            data: json!({ "seq": 4, "dir": { "x": 1.0, "y": 0.0, "z": 0.0 } }),
+        };
+        assert_eq!(GameServerMessage::from_json(&message.to_json()), Ok(message.clone()));
+        assert_eq!(GameServerMessage::from_msgpack(&message.to_msgpack()), Ok(message.clone()));
+        assert_eq!(GameServerMessage::from_binary(&message.to_msgpack()), Ok(message.clone()));
+        // older game servers send JSON in binary frames
+        assert_eq!(GameServerMessage::from_binary(message.to_json().as_bytes()), Ok(message));
+    }
+
+    #[test]
+    fn sync_world_supersedes_what_it_accounts_for() {
+        let sync = SyncWorldData {
+            planets: Vec::new(),
+            players: vec![json!({ "uuid": "alice" })],
+            boxes50cm: vec![json!({ "uuid": "box1" })],
+        };
+        let add_player = |uuid: &str| {
+            GameServerMessage::AddProps(AddPropsData {
+                planets: Vec::new(),
+                player: json!({ "uuid": uuid }),
+                request_id: None,
+            })
+        };
+        let remove = |uuid: &str| {
+            GameServerMessage::PlayerRemove(PlayerRemoveData {
+                player_uuid: uuid.to_string(),
+            })
+        };
+        let add_box = GameServerMessage::AddProp(AddPropData {
+            box50cm: json!({ "uuid": "box1" }),
+            player_uuid: "alice".to_string(),
+            request_id: None,
+        });
+
+        assert!(sync.supersedes(&add_player("alice")));
+        assert!(!sync.supersedes(&add_player("bob")));
+        assert!(sync.supersedes(&remove("bob")));
+        assert!(!sync.supersedes(&remove("alice")));
+        assert!(sync.supersedes(&add_box));
+        assert!(sync.supersedes(&GameServerMessage::SyncWorld(sync.clone())));
+        assert!(!sync.supersedes(&GameServerMessage::Ping(PingData { seq: 1, sent_at_ms: 0 })));
+    }
+}
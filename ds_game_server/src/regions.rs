@@ -0,0 +1,245 @@
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+use crate::capture::Recorder;
+use crate::config::{GameServerConfig, RegionConfig};
+use crate::link::GameServerLink;
+use crate::metrics::{LinkMetrics, Metrics};
+use crate::protocol::{GameServerMessage, SyncWorldData};
+
+/// A game server instance and the part of the world it simulates.
+pub struct Region {
+    pub config: RegionConfig,
+    pub link: Arc<GameServerLink>,
+}
+
+/// Pool of game server instances, one per region.
+///
+/// Players and props are assigned to a region when they spawn and every later
+/// command about them goes to that instance. Positions coming back are merged into
+/// a single stream before reaching `propsplugin`.
+pub struct Regions {
+    regions: Vec<Region>,
+    /// Region of each player, by props `Player.uuid`.
+    players: Mutex<HashMap<String, usize>>,
+    /// Region of each prop, by prop uuid.
+    props: Mutex<HashMap<String, usize>>,
+    /// Regions waiting for a world snapshot after a reconnect.
+    resync: Mutex<HashSet<usize>>,
+    /// Latest players and props positions of each region, not forwarded yet.
+    players_positions: Mutex<HashMap<usize, Value>>,
+    props_positions: Mutex<HashMap<usize, Value>>,
+}
+
+/// Reads the `position` of a player, planet or prop.
+pub fn position_of(value: &Value) -> Option<[f64; 3]> {
+    let position = &value["position"];
+    Some([position["x"].as_f64()?, position["y"].as_f64()?, position["z"].as_f64()?])
+}
+
+impl Regions {
+    /// Creates one link per configured region, or a single default region on
+    /// `game_server.urls` when none is configured. Their frames are captured by
+    /// `recorder` and counted in `metrics`, if any.
+    pub fn new(
+        game_server: &GameServerConfig,
+        regions: &[RegionConfig],
+        recorder: Option<Arc<Recorder>>,
+        metrics: Option<Arc<Metrics>>,
+    ) -> Self {
+        let mut configs = regions.to_vec();
+        if configs.is_empty() {
+            configs.push(RegionConfig {
+                name: "default".to_string(),
+                ..RegionConfig::default()
+            });
+        }
+        let regions = configs
+            .into_iter()
+            .map(|config| {
+                let mut link_config = game_server.clone();
+                if !config.urls.is_empty() {
+                    link_config.urls = config.urls.clone();
+                }
+                info!("🔧 DsGameServerPlugin: Region {} on {:?}", config.name, link_config.urls);
+                let mut link = GameServerLink::new(link_config);
+                if let Some(recorder) = &recorder {
+                    link = link.with_recorder(&config.name, Arc::clone(recorder));
+                }
+                if let Some(metrics) = &metrics {
+                    link = link.with_metrics(LinkMetrics::new(&config.name, Arc::clone(metrics)));
+                }
+                Region {
+                    config,
+                    link: Arc::new(link),
+                }
+            })
+            .collect();
+        Self {
+            regions,
+            players: Mutex::new(HashMap::new()),
+            props: Mutex::new(HashMap::new()),
+            resync: Mutex::new(HashSet::new()),
+            players_positions: Mutex::new(HashMap::new()),
+            props_positions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn all(&self) -> &[Region] {
+        &self.regions
+    }
+
+    pub fn get(&self, index: usize) -> &Region {
+        &self.regions[index]
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.regions.iter().position(|r| r.config.name == name)
+    }
+
+    /// Region owning whatever no other region claims.
+    pub fn default_index(&self) -> usize {
+        self.regions.iter().position(|r| r.config.is_default()).unwrap_or(0)
+    }
+
+    /// Region owning a position, the default region if none claims it.
+    pub fn region_at(&self, position: Option<[f64; 3]>) -> usize {
+        position
+            .and_then(|position| self.regions.iter().position(|r| r.config.contains(position)))
+            .unwrap_or_else(|| self.default_index())
+    }
+
+    pub fn region_of_planet(&self, planet: &Value) -> usize {
+        let name = planet["name"].as_str().unwrap_or_default();
+        self.regions
+            .iter()
+            .position(|r| r.config.planets.iter().any(|planet| planet == name))
+            .unwrap_or_else(|| self.region_at(position_of(planet)))
+    }
+
+    /// Assigns a spawning player to the region of its position.
+    pub fn assign_player(&self, player: &Value) -> usize {
+        let index = self.region_at(position_of(player));
+        if let Some(uuid) = player["uuid"].as_str() {
+            self.players.lock().unwrap().insert(uuid.to_string(), index);
+        }
+        index
+    }
+
+    /// Region of a player, the default region if it never spawned.
+    pub fn player_region(&self, player_uuid: &str) -> usize {
+        self.players.lock().unwrap().get(player_uuid).copied().unwrap_or_else(|| self.default_index())
+    }
+
+    /// Makes another region the owner of a player, once its handoff is committed.
+    pub fn move_player(&self, player_uuid: &str, index: usize) {
+        self.players.lock().unwrap().insert(player_uuid.to_string(), index);
+    }
+
+    /// Whether a region simulates this player, true for players it does not know.
+    fn owns_player(&self, index: usize, player: &Value) -> bool {
+        let owner = player["uuid"].as_str().and_then(|uuid| self.players.lock().unwrap().get(uuid).copied());
+        owner.is_none_or(|owner| owner == index)
+    }
+
+    pub fn forget_player(&self, player_uuid: &str) -> usize {
+        self.players.lock().unwrap().remove(player_uuid).unwrap_or_else(|| self.default_index())
+    }
+
+    pub fn assign_prop(&self, prop_uuid: &str, index: usize) {
+        self.props.lock().unwrap().insert(prop_uuid.to_string(), index);
+    }
+
+    fn prop_region(&self, prop: &Value) -> usize {
+        let known = prop["uuid"].as_str().and_then(|uuid| self.props.lock().unwrap().get(uuid).copied());
+        known.unwrap_or_else(|| self.region_at(position_of(prop)))
+    }
+
+    pub fn request_resync(&self, index: usize) {
+        self.resync.lock().unwrap().insert(index);
+    }
+
+    /// Regions waiting for a snapshot, none if no link came back since the last one.
+    pub fn take_resync(&self) -> Vec<usize> {
+        self.resync.lock().unwrap().drain().collect()
+    }
+
+    /// Splits a world snapshot into the part owned by each region.
+    pub fn split_snapshot(&self, snapshot: SyncWorldData) -> Vec<SyncWorldData> {
+        let mut parts: Vec<SyncWorldData> = (0..self.regions.len())
+            .map(|_| SyncWorldData { planets: Vec::new(), players: Vec::new(), boxes50cm: Vec::new() })
+            .collect();
+        for planet in snapshot.planets {
+            parts[self.region_of_planet(&planet)].planets.push(planet);
+        }
+        for player in snapshot.players {
+            let known = player["uuid"].as_str().and_then(|uuid| self.players.lock().unwrap().get(uuid).copied());
+            let index = known.unwrap_or_else(|| self.assign_player(&player));
+            parts[index].players.push(player);
+        }
+        for prop in snapshot.boxes50cm {
+            parts[self.prop_region(&prop)].boxes50cm.push(prop);
+        }
+        parts
+    }
+
+    /// Keeps the positions received from a region until the next tick. With a single
+    /// region there is nothing to merge and the message is returned to forward it now.
+    ///
+    /// Only the players a region owns are kept: during a handoff both instances
+    /// simulate the player, the new one takes over at the commit.
+    pub fn store_positions(&self, index: usize, message: GameServerMessage) -> Option<GameServerMessage> {
+        if self.regions.len() == 1 {
+            return Some(message);
+        }
+        match message {
+            GameServerMessage::PlayersPosition(mut data) => {
+                if let Value::Array(players) = &mut data {
+                    players.retain(|player| self.owns_player(index, player));
+                }
+                self.players_positions.lock().unwrap().insert(index, data);
+                None
+            }
+            GameServerMessage::PropsPosition(data) => {
+                self.props_positions.lock().unwrap().insert(index, data);
+                None
+            }
+            other => Some(other),
+        }
+    }
+
+    /// Forgets the positions of a region whose link is down, they are stale.
+    pub fn drop_positions(&self, index: usize) {
+        self.players_positions.lock().unwrap().remove(&index);
+        self.props_positions.lock().unwrap().remove(&index);
+    }
+
+    /// Latest positions of every region since the last call, merged in one message each.
+    pub fn take_merged_positions(&self) -> Vec<GameServerMessage> {
+        let mut merged = Vec::new();
+        if let Some(players) = merge(&self.players_positions) {
+            merged.push(GameServerMessage::PlayersPosition(players));
+        }
+        if let Some(props) = merge(&self.props_positions) {
+            merged.push(GameServerMessage::PropsPosition(props));
+        }
+        merged
+    }
+}
+
+fn merge(positions: &Mutex<HashMap<usize, Value>>) -> Option<Value> {
+    let mut positions = positions.lock().unwrap();
+    if positions.is_empty() {
+        return None;
+    }
+    let mut merged = Vec::new();
+    for (_, data) in positions.drain() {
+        match data {
+            Value::Array(items) => merged.extend(items),
+            item => merged.push(item),
+        }
+    }
+    Some(Value::Array(merged))
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+struct Pending {
+    /// `event` of the command, e.g. `add_prop`.
+    command: &'static str,
+    /// When the writer put it on the wire, `None` while it waits in the outbox.
+    written_at: Option<Instant>,
+}
+
+/// Commands sent to the game server that wait for a `server`/`ack` or `server`/`nack`.
+///
+/// The timeout only starts once a command is written, so commands queued while the
+/// game server is unreachable are not reported as failed.
+#[derive(Default)]
+pub struct PendingRequests {
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl PendingRequests {
+    pub fn track(&self, request_id: &str, command: &'static str) {
+        let pending = Pending { command, written_at: None };
+        self.pending.lock().unwrap().insert(request_id.to_string(), pending);
+    }
+
+    pub fn written(&self, request_id: &str) {
+        if let Some(pending) = self.pending.lock().unwrap().get_mut(request_id) {
+            pending.written_at = Some(Instant::now());
+        }
+    }
+
+    /// Removes a request answered by the game server and returns its command,
+    /// `None` if it is unknown or already timed out.
+    pub fn resolve(&self, request_id: &str) -> Option<&'static str> {
+        self.pending.lock().unwrap().remove(request_id).map(|pending| pending.command)
+    }
+
+    /// Removes and returns the requests written more than `timeout` ago.
+    pub fn expired(&self, timeout: Duration) -> Vec<(String, &'static str)> {
+        let mut pending = self.pending.lock().unwrap();
+        let expired: Vec<String> = pending
+            .iter()
+            .filter(|(_, p)| p.written_at.is_some_and(|at| at.elapsed() > timeout))
+            .map(|(id, _)| id.clone())
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|id| pending.remove(&id).map(|p| (id, p.command)))
+            .collect()
+    }
+
+    /// Removes and returns the requests written on a connection that was lost: their
+    /// answer will never come, and the world resync after the reconnect sends them again.
+    pub fn connection_lost(&self) -> Vec<(String, &'static str)> {
+        let mut lost = Vec::new();
+        self.pending.lock().unwrap().retain(|id, p| {
+            if p.written_at.is_some() {
+                debug!("[link] dropping {} request {} of the lost connection", p.command, id);
+                lost.push((id.clone(), p.command));
+            }
+            p.written_at.is_none()
+        });
+        lost
+    }
+}
@@ -0,0 +1,63 @@
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ClientConfig, RootCertStore};
+use std::sync::Arc;
+use tokio_tungstenite::Connector;
+use tracing::warn;
+
+use crate::config::GameServerConfig;
+
+/// Builds the TLS settings of `wss://` connections.
+///
+/// The game server certificate is checked against the CAs of `tls_ca_path`, or the
+/// system trust store when it is not set. A client certificate is presented when
+/// `tls_cert_path` and `tls_key_path` are set, for game servers requiring mutual TLS.
+pub fn connector(config: &GameServerConfig) -> Result<Connector, String> {
+    let mut roots = RootCertStore::empty();
+    if config.tls_ca_path.is_empty() {
+        let native = rustls_native_certs::load_native_certs();
+        for e in &native.errors {
+            warn!("Cannot load a system CA certificate: {}", e);
+        }
+        let (added, _ignored) = roots.add_parsable_certificates(native.certs);
+        if added == 0 {
+            return Err("no CA certificate in the system store, set tls_ca_path".to_string());
+        }
+    } else {
+        for cert in read_certs(&config.tls_ca_path)? {
+            roots
+                .add(cert)
+                .map_err(|e| format!("invalid CA certificate in {}: {}", config.tls_ca_path, e))?;
+        }
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_root_certificates(roots);
+    let tls = match (config.tls_cert_path.is_empty(), config.tls_key_path.is_empty()) {
+        (true, true) => builder.with_no_client_auth(),
+        (false, false) => {
+            let certs = read_certs(&config.tls_cert_path)?;
+            let key = PrivateKeyDer::from_pem_file(&config.tls_key_path)
+                .map_err(|e| format!("cannot read private key {}: {}", config.tls_key_path, e))?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|e| format!("invalid client certificate: {}", e))?
+        }
+        _ => return Err("tls_cert_path and tls_key_path must be set together".to_string()),
+    };
+    Ok(Connector::Rustls(Arc::new(tls)))
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(|e| format!("cannot read {}: {}", path, e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid certificate in {}: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate in {}", path));
+    }
+    Ok(certs)
+}
@@ -0,0 +1,332 @@
+//! Runs the game server link against `mock_game_server`: acks, heartbeat, reconnect
+//! with resync and handoffs between two instances.
+
+use plugin_ds_game_server::config::{GameServerConfig, HandoffConfig, RegionConfig};
+use plugin_ds_game_server::handoff::Handoffs;
+use plugin_ds_game_server::link::{GameServerLink, LinkEvent};
+use plugin_ds_game_server::protocol::{AddPropData, AddPropsData, GameServerMessage, SyncWorldData};
+use plugin_ds_game_server::regions::{position_of, Regions};
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+
+/// Longest wait for anything the mock is expected to do.
+const WAIT: Duration = Duration::from_secs(10);
+
+/// A `mock_game_server` process, killed when dropped.
+struct Mock {
+    child: Child,
+}
+
+impl Mock {
+    fn start(port: u16) -> Self {
+        let child = Command::new(env!("CARGO_BIN_EXE_mock_game_server"))
+            .args(["--listen", &format!("127.0.0.1:{}", port), "--tick-ms", "20"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("mock_game_server starts");
+        Self { child }
+    }
+
+    fn signal(&self, signal: libc::c_int) {
+        assert_eq!(unsafe { libc::kill(self.child.id() as libc::pid_t, signal) }, 0);
+    }
+}
+
+impl Drop for Mock {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn config(port: u16) -> GameServerConfig {
+    GameServerConfig {
+        urls: vec![format!("ws://127.0.0.1:{}", port)],
+        initial_backoff_ms: 50,
+        max_backoff_ms: 200,
+        ack_timeout_ms: 2000,
+        ..GameServerConfig::default()
+    }
+}
+
+fn player(uuid: &str, x: f64) -> Value {
+    json!({
+        "name": uuid,
+        "uuid": uuid,
+        "internal_uuid": format!("internal-{}", uuid),
+        "position": { "x": x, "y": 0.0, "z": 0.0 },
+        "rotation": { "x": 0.0, "y": 0.0, "z": 0.0 },
+    })
+}
+
+fn add_player(uuid: &str, x: f64) -> GameServerMessage {
+    GameServerMessage::AddProps(AddPropsData {
+        planets: Vec::new(),
+        player: player(uuid, x),
+        request_id: Some(uuid.to_string()),
+    })
+}
+
+/// Waits for the first event `matches` accepts, skipping the others.
+async fn wait_for<T>(events: &mut mpsc::Receiver<LinkEvent>, mut matches: impl FnMut(LinkEvent) -> Option<T>) -> T {
+    let waiting = async {
+        loop {
+            let event = events.recv().await.expect("link events stop only with the link");
+            if let Some(found) = matches(event) {
+                return found;
+            }
+        }
+    };
+    tokio::time::timeout(WAIT, waiting).await.expect("expected link event")
+}
+
+async fn connected(events: &mut mpsc::Receiver<LinkEvent>) -> bool {
+    wait_for(events, |event| match event {
+        LinkEvent::Connected { reconnect } => Some(reconnect),
+        _ => None,
+    })
+    .await
+}
+
+async fn command_result(events: &mut mpsc::Receiver<LinkEvent>, id: &str) -> Result<(), String> {
+    wait_for(events, |event| match event {
+        LinkEvent::CommandResult { request_id, result, .. } if request_id == id => Some(result),
+        _ => None,
+    })
+    .await
+}
+
+/// Whether a position update lists the body with this uuid.
+fn reports(positions: &Value, uuid: &str) -> bool {
+    positions.as_array().is_some_and(|items| items.iter().any(|item| item["uuid"] == uuid))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn acks_and_nacks_resolve_their_command() {
+    let port = free_port();
+    let _mock = Mock::start(port);
+    let link = Arc::new(GameServerLink::new(config(port)));
+    let mut events = link.start(&Handle::current()).unwrap();
+    assert!(!connected(&mut events).await);
+
+    link.send_message(&add_player("alice", 0.0));
+    assert_eq!(command_result(&mut events, "alice").await, Ok(()));
+
+    // the mock refuses a prop without uuid
+    link.send_message(&GameServerMessage::AddProp(AddPropData {
+        box50cm: json!({ "position": { "x": 0.0, "y": 0.0, "z": 0.0 } }),
+        player_uuid: "alice".to_string(),
+        request_id: Some("box-1".to_string()),
+    }));
+    let result = command_result(&mut events, "box-1").await;
+    assert!(result.is_err_and(|reason| reason.contains("uuid")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn silent_game_server_is_dropped_by_the_heartbeat() {
+    let port = free_port();
+    let mock = Mock::start(port);
+    let link = Arc::new(GameServerLink::new(GameServerConfig {
+        heartbeat_interval_ms: 100,
+        heartbeat_timeout_ms: 500,
+        ..config(port)
+    }));
+    let mut events = link.start(&Handle::current()).unwrap();
+    assert!(!connected(&mut events).await);
+
+    // the connection stays open, but nothing comes through anymore
+    mock.signal(libc::SIGSTOP);
+    let reason = wait_for(&mut events, |event| match event {
+        LinkEvent::Disconnected { reason } => Some(reason),
+        _ => None,
+    })
+    .await;
+    assert!(reason.contains("no heartbeat"), "{}", reason);
+
+    mock.signal(libc::SIGCONT);
+    assert!(connected(&mut events).await);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconnect_resyncs_the_world_once() {
+    let port = free_port();
+    let first = Mock::start(port);
+    let link = Arc::new(GameServerLink::new(config(port)));
+    let mut events = link.start(&Handle::current()).unwrap();
+    assert!(!connected(&mut events).await);
+    link.send_message(&add_player("alice", 0.0));
+    assert_eq!(command_result(&mut events, "alice").await, Ok(()));
+
+    // the game server restarts with an empty scene, a prop spawned meanwhile waits
+    drop(first);
+    wait_for(&mut events, |event| matches!(event, LinkEvent::Disconnected { .. }).then_some(())).await;
+    let prop = json!({ "uuid": "box-1", "position": { "x": 1.0, "y": 0.0, "z": 0.0 } });
+    link.send_message(&GameServerMessage::AddProp(AddPropData {
+        box50cm: prop.clone(),
+        player_uuid: "alice".to_string(),
+        request_id: Some("box-1".to_string()),
+    }));
+    let _second = Mock::start(port);
+    assert!(connected(&mut events).await);
+
+    link.resync(SyncWorldData {
+        planets: Vec::new(),
+        players: vec![player("alice", 0.0)],
+        boxes50cm: vec![prop],
+    });
+    // the prop is spawned by the snapshot, or by the command if it was flushed first
+    assert_eq!(command_result(&mut events, "box-1").await, Ok(()));
+    let (mut player_seen, mut prop_seen) = (false, false);
+    wait_for(&mut events, |event| {
+        match event {
+            LinkEvent::Message(GameServerMessage::PlayersPosition(players)) => player_seen |= reports(&players, "alice"),
+            LinkEvent::Message(GameServerMessage::PropsPosition(props)) => prop_seen |= reports(&props, "box-1"),
+            LinkEvent::CommandResult { request_id, .. } => panic!("{} answered twice", request_id),
+            _ => {}
+        }
+        (player_seen && prop_seen).then_some(())
+    })
+    .await;
+    assert_eq!(link.queued(), 0);
+}
+
+/// Two instances: `west` owns everything, `east` the area from x = 100.
+fn two_regions(west: u16, east: u16, handoff: HandoffConfig) -> (Arc<Regions>, Arc<Handoffs>) {
+    let regions = vec![
+        RegionConfig {
+            name: "west".to_string(),
+            urls: config(west).urls,
+            ..RegionConfig::default()
+        },
+        RegionConfig {
+            name: "east".to_string(),
+            urls: config(east).urls,
+            min: Some([100.0, -1000.0, -1000.0]),
+            max: Some([1000.0, 1000.0, 1000.0]),
+            ..RegionConfig::default()
+        },
+    ];
+    let regions = Regions::new(&config(west), &regions, None, None);
+    (Arc::new(regions), Arc::new(Handoffs::new(handoff)))
+}
+
+/// Events of every started region, tagged with its index.
+fn start_regions(regions: &Regions, started: &[usize]) -> mpsc::Receiver<(usize, LinkEvent)> {
+    let (tx, rx) = mpsc::channel(1024);
+    for &index in started {
+        let mut events = regions.get(index).link.start(&Handle::current()).unwrap();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if tx.send((index, event)).await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+    rx
+}
+
+/// Routes the handoff messages like the plugin does, until `until` returns a value.
+async fn run_handoffs<T>(
+    regions: &Regions,
+    handoffs: &Handoffs,
+    events: &mut mpsc::Receiver<(usize, LinkEvent)>,
+    mut until: impl FnMut(usize, &GameServerMessage) -> Option<T>,
+) -> T {
+    let running = async {
+        loop {
+            let (index, event) = events.recv().await.unwrap();
+            handoffs.abort_expired(regions);
+            let LinkEvent::Message(message) = event else {
+                continue;
+            };
+            let found = until(index, &message);
+            match message {
+                GameServerMessage::PlayersPosition(players) => handoffs.check_crossings(regions, index, &players),
+                GameServerMessage::HandoffState(data) => handoffs.on_state(regions, index, data),
+                GameServerMessage::HandoffReady(data) => handoffs.on_ready(regions, index, data),
+                _ => {}
+            }
+            if let Some(found) = found {
+                return found;
+            }
+        }
+    };
+    tokio::time::timeout(WAIT, running).await.expect("expected handoff step")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn handoff_commits_to_the_region_entered() {
+    let (west_port, east_port) = (free_port(), free_port());
+    let (_west, _east) = (Mock::start(west_port), Mock::start(east_port));
+    let (regions, handoffs) = two_regions(west_port, east_port, HandoffConfig::default());
+    let mut events = start_regions(&regions, &[0, 1]);
+
+    // spawned in the west, but already across the border
+    regions.move_player("alice", 0);
+    regions.get(0).link.send_message(&add_player("alice", 150.0));
+
+    run_handoffs(&regions, &handoffs, &mut events, |index, message| match message {
+        GameServerMessage::PlayersPosition(players) if index == 1 && reports(players, "alice") => Some(()),
+        _ => None,
+    })
+    .await;
+    assert_eq!(regions.player_region("alice"), 1);
+    // the old instance removed it at the commit
+    run_handoffs(&regions, &handoffs, &mut events, |index, message| match message {
+        GameServerMessage::PlayersPosition(players) if index == 0 && !reports(players, "alice") => Some(()),
+        _ => None,
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn handoff_aborts_when_the_region_entered_does_not_answer() {
+    let (west_port, east_port) = (free_port(), free_port());
+    let _west = Mock::start(west_port);
+    let handoff = HandoffConfig {
+        timeout_ms: 300,
+        ..HandoffConfig::default()
+    };
+    let (regions, handoffs) = two_regions(west_port, east_port, handoff);
+    // the east instance is never reached
+    let mut events = start_regions(&regions, &[0]);
+
+    regions.move_player("alice", 0);
+    let west = &regions.get(0).link;
+    west.send_message(&add_player("alice", 150.0));
+    west.send_message(&GameServerMessage::PlayerMove {
+        player_id: "internal-alice".to_string(),
+        data: json!({ "seq": 1, "dir": { "x": 1.0, "y": 0.0, "z": 0.0 } }),
+    });
+    west.tick();
+
+    let frozen_at = run_handoffs(&regions, &handoffs, &mut events, |_, message| match message {
+        GameServerMessage::HandoffState(data) => position_of(&data.player).map(|p| p[0]),
+        _ => None,
+    })
+    .await;
+    // the player stays in the west and moves again once the handoff is aborted
+    run_handoffs(&regions, &handoffs, &mut events, |_, message| match message {
+        GameServerMessage::PlayersPosition(players) => players
+            .as_array()?
+            .iter()
+            .find(|player| player["uuid"] == "alice")
+            .and_then(position_of)
+            .filter(|position| position[0] > frozen_at + 0.5),
+        _ => None,
+    })
+    .await;
+    assert_eq!(regions.player_region("alice"), 0);
+}
@@ -0,0 +1,267 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::AccountsConfig;
+
+/// Longest login accepted, in characters.
+const MAX_LOGIN_LEN: usize = 32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    /// Argon2id hash in PHC format, salt and parameters included.
+    pub password_hash: String,
+    pub created_at: String,
+    /// `Player.uuid` of the account, the same on every login. Given on the first login
+    /// to the accounts created before it existed.
+    #[serde(default)]
+    pub player_uuid: Option<Uuid>,
+}
+
+/// Content of the accounts file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AccountsFile {
+    accounts: BTreeMap<String, Account>,
+}
+
+/// Why a login is refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginError {
+    /// The login is empty, too long or has forbidden characters.
+    InvalidLogin,
+    /// Unknown login or wrong password, on purpose not told apart.
+    BadCredentials,
+    /// The accounts could not be read or written.
+    Unavailable(String),
+    /// No configured provider handles this kind of credentials.
+    Unsupported,
+}
+
+impl fmt::Display for LoginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoginError::InvalidLogin => write!(f, "invalid login"),
+            LoginError::BadCredentials => write!(f, "bad credentials"),
+            LoginError::Unavailable(reason) => write!(f, "accounts unavailable: {}", reason),
+            LoginError::Unsupported => write!(f, "no provider for these credentials"),
+        }
+    }
+}
+
+impl std::error::Error for LoginError {}
+
+/// Hashes a password with Argon2id and a random salt.
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Checks a password against a PHC hash. The comparison is constant time.
+fn verify_password(hash: &str, password: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default().verify_password(password.as_bytes(), &hash).is_ok(),
+        Err(e) => {
+            warn!("🔧 DsPlayerAuthenticationPlugin: Invalid password hash in the accounts: {}", e);
+            false
+        }
+    }
+}
+
+pub fn valid_login(login: &str) -> bool {
+    !login.is_empty()
+        && login.chars().count() <= MAX_LOGIN_LEN
+        && login.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Accounts as last read from the file, with its modification time.
+type Loaded = (BTreeMap<String, Account>, Option<SystemTime>);
+
+/// Creates a file only its owner can read, the accounts file holds password hashes.
+#[cfg(unix)]
+fn create_private(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+/// Accounts stored in a local JSON file.
+///
+/// Verifying a password is deliberately slow (Argon2id), so [`AccountStore::login`]
+/// must not run on an event handler thread. The file is read again when it changed,
+/// so accounts added with `ds_accounts` work without a restart.
+pub struct AccountStore {
+    path: PathBuf,
+    register_unknown: bool,
+    accounts: Mutex<Loaded>,
+    /// Verified for unknown logins, so they take as long as known ones.
+    dummy_hash: String,
+}
+
+impl AccountStore {
+    pub fn open(config: &AccountsConfig) -> Result<Self, String> {
+        let store = Self {
+            path: PathBuf::from(&config.path),
+            register_unknown: config.register_unknown,
+            accounts: Mutex::new((BTreeMap::new(), None)),
+            dummy_hash: hash_password("not a password")?,
+        };
+        let count = {
+            let mut accounts = store.accounts.lock().unwrap();
+            store.refresh(&mut accounts)?;
+            accounts.0.len()
+        };
+        info!("🔧 DsPlayerAuthenticationPlugin: {} accounts in {}", count, store.path.display());
+        Ok(store)
+    }
+
+    /// Reloads the file if its modification time changed since it was last read.
+    fn refresh(&self, accounts: &mut Loaded) -> Result<(), String> {
+        let modified = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.modified().ok(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(format!("cannot read {}: {}", self.path.display(), e)),
+        };
+        if modified.is_some() && modified != accounts.1 {
+            let content = std::fs::read_to_string(&self.path).map_err(|e| format!("cannot read {}: {}", self.path.display(), e))?;
+            let file: AccountsFile = serde_json::from_str(&content).map_err(|e| format!("invalid {}: {}", self.path.display(), e))?;
+            *accounts = (file.accounts, modified);
+        }
+        Ok(())
+    }
+
+    /// Writes the accounts to a temporary file then renames it, so a crash never
+    /// leaves a truncated file.
+    fn save(&self, accounts: &mut Loaded) -> Result<(), String> {
+        let file = AccountsFile { accounts: accounts.0.clone() };
+        let content = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        let temporary = self.path.with_extension("json.tmp");
+        // left over by a crash, maybe with other permissions
+        let _ = std::fs::remove_file(&temporary);
+        create_private(&temporary)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(|e| format!("cannot write {}: {}", temporary.display(), e))?;
+        std::fs::rename(&temporary, &self.path).map_err(|e| format!("cannot write {}: {}", self.path.display(), e))?;
+        accounts.1 = std::fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+        Ok(())
+    }
+
+    /// Checks the credentials of a player, creating the account first if the login is
+    /// unknown and `register_unknown` is set. Returns the `player_uuid` of the account.
+    pub fn login(&self, login: &str, password: &str) -> Result<Uuid, LoginError> {
+        if !valid_login(login) {
+            return Err(LoginError::InvalidLogin);
+        }
+        let hash = {
+            let mut accounts = self.accounts.lock().unwrap();
+            self.refresh(&mut accounts).map_err(LoginError::Unavailable)?;
+            accounts.0.get(login).map(|account| account.password_hash.clone())
+        };
+        // the lock is not held while verifying, other logins go on meanwhile
+        match hash {
+            Some(hash) if verify_password(&hash, password) => self.player_uuid(login).map_err(LoginError::Unavailable),
+            Some(_) => Err(LoginError::BadCredentials),
+            None if self.register_unknown && !password.is_empty() => {
+                let player_uuid = self.register(login, password)?;
+                info!("🔧 DsPlayerAuthenticationPlugin: Registered account {}", login);
+                Ok(player_uuid)
+            }
+            None => {
+                let _ = verify_password(&self.dummy_hash, password);
+                Err(LoginError::BadCredentials)
+            }
+        }
+    }
+
+    /// `player_uuid` of an account, given and saved if it has none yet.
+    fn player_uuid(&self, login: &str) -> Result<Uuid, String> {
+        let mut accounts = self.accounts.lock().unwrap();
+        // the file may have changed while the password was verified, saving must not undo that
+        self.refresh(&mut accounts)?;
+        let account = accounts.0.get_mut(login).ok_or_else(|| format!("account {} removed", login))?;
+        if let Some(player_uuid) = account.player_uuid {
+            return Ok(player_uuid);
+        }
+        let player_uuid = Uuid::new_v4();
+        account.player_uuid = Some(player_uuid);
+        self.save(&mut accounts)?;
+        Ok(player_uuid)
+    }
+
+    /// Creates the account of an unknown login. Refused if it was created meanwhile,
+    /// by a concurrent first login for instance, rather than changing its password.
+    fn register(&self, login: &str, password: &str) -> Result<Uuid, LoginError> {
+        let password_hash = hash_password(password).map_err(LoginError::Unavailable)?;
+        let mut accounts = self.accounts.lock().unwrap();
+        self.refresh(&mut accounts).map_err(LoginError::Unavailable)?;
+        if accounts.0.contains_key(login) {
+            return Err(LoginError::BadCredentials);
+        }
+        let player_uuid = Uuid::new_v4();
+        let account = Account {
+            password_hash,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            player_uuid: Some(player_uuid),
+        };
+        accounts.0.insert(login.to_string(), account);
+        self.save(&mut accounts).map_err(LoginError::Unavailable)?;
+        Ok(player_uuid)
+    }
+
+    /// Creates an account, or changes its password if it exists.
+    pub fn set_password(&self, login: &str, password: &str) -> Result<(), String> {
+        if !valid_login(login) {
+            return Err(format!("invalid login {:?}", login));
+        }
+        if password.is_empty() {
+            return Err("empty password".to_string());
+        }
+        let password_hash = hash_password(password)?;
+        let mut accounts = self.accounts.lock().unwrap();
+        self.refresh(&mut accounts)?;
+        let account = match accounts.0.remove(login) {
+            Some(account) => Account { password_hash, ..account },
+            None => Account {
+                password_hash,
+                created_at: chrono::Utc::now().to_rfc3339(),
+                player_uuid: Some(Uuid::new_v4()),
+            },
+        };
+        accounts.0.insert(login.to_string(), account);
+        self.save(&mut accounts)
+    }
+
+    /// Deletes an account, returns false if it did not exist.
+    pub fn remove(&self, login: &str) -> Result<bool, String> {
+        let mut accounts = self.accounts.lock().unwrap();
+        self.refresh(&mut accounts)?;
+        if accounts.0.remove(login).is_none() {
+            return Ok(false);
+        }
+        self.save(&mut accounts)?;
+        Ok(true)
+    }
+
+    /// Copy of every account, for listing them.
+    pub fn accounts(&self) -> Result<BTreeMap<String, Account>, String> {
+        let mut accounts = self.accounts.lock().unwrap();
+        self.refresh(&mut accounts)?;
+        Ok(accounts.0.clone())
+    }
+}
@@ -0,0 +1,61 @@
+//! Manages the local accounts players log in with.
+//!
+//! The password is read from the first line of stdin so it stays out of the shell
+//! history. The accounts file is the one of the plugin settings unless `--file` is given.
+//!
+//! ```bash
+//! echo 'secret' | cargo run --bin ds_accounts -- add alice
+//! cargo run --bin ds_accounts -- list
+//! ```
+
+use plugin_ds_player_authentication::accounts::AccountStore;
+use plugin_ds_player_authentication::config::PluginConfig;
+use std::io::BufRead;
+
+const USAGE: &str = "usage: ds_accounts [--file PATH] add LOGIN | remove LOGIN | list";
+
+fn read_password() -> Result<String, String> {
+    let mut password = String::new();
+    std::io::stdin().lock().read_line(&mut password).map_err(|e| e.to_string())?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn run() -> Result<(), String> {
+    let mut config = PluginConfig::load().accounts;
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--file") {
+        if args.len() < 2 {
+            return Err("--file needs a path".to_string());
+        }
+        config.path = args.remove(1);
+        args.remove(0);
+    }
+    let store = AccountStore::open(&config)?;
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["add", login] => {
+            store.set_password(login, &read_password()?)?;
+            println!("Account {} saved in {}", login, config.path);
+        }
+        ["remove", login] => {
+            if !store.remove(login)? {
+                return Err(format!("no account {}", login));
+            }
+            println!("Account {} removed from {}", login, config.path);
+        }
+        ["list"] => {
+            for (login, account) in store.accounts()? {
+                let player_uuid = account.player_uuid.map(|uuid| uuid.to_string()).unwrap_or_default();
+                println!("{}\t{}", login, player_uuid);
+            }
+        }
+        _ => return Err("unknown command".to_string()),
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}\n{}", e, USAGE);
+        std::process::exit(2);
+    }
+}
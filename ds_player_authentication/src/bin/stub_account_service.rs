@@ -0,0 +1,64 @@
+//! Stub of the account system for local development, see [`plugin_ds_player_authentication::stub`].
+//!
+//! ```bash
+//! cargo run --bin stub_account_service -- --listen 127.0.0.1:8990 --api-key dev --token abc=ddurieux
+//! ```
+
+use plugin_ds_player_authentication::stub::{Fault, StubAccountService};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const USAGE: &str = "usage: stub_account_service [--listen ADDR] [--api-key KEY] [--token TOKEN=USERNAME]... \
+                     [--ttl-secs SECS] [--fail-first N] [--delay-ms MS]";
+
+fn from_args() -> Result<(String, StubAccountService), String> {
+    let mut listen = "127.0.0.1:8990".to_string();
+    let mut stub = StubAccountService {
+        api_key: std::env::var("DS_PLAYER_AUTH_EXTERNAL_API_KEY").unwrap_or_default(),
+        ..StubAccountService::default()
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => listen = args.next().ok_or("--listen needs an address")?,
+            "--api-key" => stub.api_key = args.next().ok_or("--api-key needs a value")?,
+            "--token" => {
+                let token = args.next().ok_or("--token needs TOKEN=USERNAME")?;
+                let (token, username) = token.split_once('=').ok_or("--token needs TOKEN=USERNAME")?;
+                stub.tokens.insert(token.to_string(), username.to_string());
+            }
+            "--ttl-secs" => stub.ttl_secs = args.next().and_then(|s| s.parse().ok()).ok_or("--ttl-secs needs seconds")?,
+            "--fail-first" => {
+                let count: usize = args.next().and_then(|n| n.parse().ok()).ok_or("--fail-first needs a count")?;
+                stub.faults.get_mut().unwrap().extend(std::iter::repeat_n(Fault::Status(503), count));
+            }
+            "--delay-ms" => {
+                let ms = args.next().and_then(|ms| ms.parse().ok()).ok_or("--delay-ms needs milliseconds")?;
+                stub.delay = Duration::from_millis(ms);
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    Ok((listen, stub))
+}
+
+#[tokio::main]
+async fn main() {
+    let (listen, stub) = match from_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let listener = match TcpListener::bind(&listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Cannot listen on {}: {}", listen, e);
+            std::process::exit(1);
+        }
+    };
+    println!("Stub account service on http://{} with {} tokens", listen, stub.tokens.len());
+    Arc::new(stub).run(listener).await;
+}
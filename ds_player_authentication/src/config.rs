@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+/// Name of the settings file looked up next to the plugin library.
+const CONFIG_FILE_NAME: &str = "ds_player_authentication.toml";
+/// Environment variable pointing to an explicit settings file.
+const CONFIG_PATH_ENV: &str = "DS_PLAYER_AUTH_CONFIG";
+
+/// Settings of the ds_player_authentication plugin.
+///
+/// Loaded from `ds_player_authentication.toml` next to the plugin library (or the file
+/// pointed by `DS_PLAYER_AUTH_CONFIG`), then overridden by `DS_PLAYER_AUTH_*`
+/// environment variables. Every field has a default so the file is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginConfig {
+    pub auth: AuthConfig,
+    pub accounts: AccountsConfig,
+    pub external_auth: ExternalAuthConfig,
+}
+
+/// Backend of the logins, see `provider`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    /// The accounts file of `[accounts]`, for password logins.
+    Local,
+    /// The account service of `[external_auth]`, for token logins.
+    Http,
+    /// Accepts any login, never in production.
+    Dev,
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(ProviderKind::Local),
+            "http" => Ok(ProviderKind::Http),
+            "dev" => Ok(ProviderKind::Dev),
+            _ => Err(format!("unknown provider {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Tried in order, a login goes to the first provider handling its kind of credentials.
+    pub providers: Vec<ProviderKind>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            providers: vec![ProviderKind::Local, ProviderKind::Http],
+        }
+    }
+}
+
+/// Local accounts players log in with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountsConfig {
+    /// JSON file holding the accounts and their password hashes, created when needed.
+    pub path: String,
+    /// Creates the account of an unknown login on its first connection, with the
+    /// password it gave. Only meant for local development.
+    pub register_unknown: bool,
+}
+
+impl Default for AccountsConfig {
+    fn default() -> Self {
+        Self {
+            path: "accounts.json".to_string(),
+            register_unknown: false,
+        }
+    }
+}
+
+/// Account service validating the tokens of `auth`/`login`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExternalAuthConfig {
+    /// Base URL of the service, `POST {base_url}/auth/validate` is called. Empty
+    /// disables token logins.
+    pub base_url: String,
+    /// Sent as `Authorization: Bearer`.
+    pub api_key: String,
+    pub game_id: String,
+    /// Time allowed for each attempt, connection included.
+    pub timeout_ms: u64,
+    /// Attempts made again after a timeout, a network error or a 5xx answer.
+    pub retries: u32,
+    /// Wait before the first retry, doubled on each following one.
+    pub retry_backoff_ms: u64,
+}
+
+impl Default for ExternalAuthConfig {
+    fn default() -> Self {
+        Self {
+            base_url: String::new(),
+            api_key: String::new(),
+            game_id: "DyingStar".to_string(),
+            timeout_ms: 5000,
+            retries: 2,
+            retry_backoff_ms: 250,
+        }
+    }
+}
+
+impl PluginConfig {
+    /// Loads the settings file if any, then applies environment overrides.
+    /// A missing or invalid file falls back to the defaults.
+    pub fn load() -> Self {
+        let mut config = match config_path() {
+            Some(path) if path.exists() => Self::from_file(&path).unwrap_or_else(|e| {
+                warn!("🔧 DsPlayerAuthenticationPlugin: Ignoring invalid config {}: {}", path.display(), e);
+                Self::default()
+            }),
+            _ => Self::default(),
+        };
+        config.apply_env_overrides();
+        config
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let config = toml::from_str(&content).map_err(|e| e.to_string())?;
+        info!("🔧 DsPlayerAuthenticationPlugin: Loaded config from {}", path.display());
+        Ok(config)
+    }
+
+    fn apply_env_overrides(&mut self) {
+        if let Ok(providers) = std::env::var("DS_PLAYER_AUTH_PROVIDERS") {
+            let providers: Result<Vec<ProviderKind>, String> = providers
+                .split(',')
+                .map(str::trim)
+                .filter(|provider| !provider.is_empty())
+                .map(str::parse)
+                .collect();
+            match providers {
+                Ok(providers) if !providers.is_empty() => self.auth.providers = providers,
+                Ok(_) => {}
+                Err(e) => warn!("🔧 DsPlayerAuthenticationPlugin: Ignoring DS_PLAYER_AUTH_PROVIDERS: {}", e),
+            }
+        }
+        override_from_env("DS_PLAYER_AUTH_ACCOUNTS_PATH", &mut self.accounts.path);
+        override_from_env("DS_PLAYER_AUTH_REGISTER_UNKNOWN", &mut self.accounts.register_unknown);
+        override_from_env("DS_PLAYER_AUTH_EXTERNAL_BASE_URL", &mut self.external_auth.base_url);
+        override_from_env("DS_PLAYER_AUTH_EXTERNAL_API_KEY", &mut self.external_auth.api_key);
+        override_from_env("DS_PLAYER_AUTH_EXTERNAL_GAME_ID", &mut self.external_auth.game_id);
+        override_from_env("DS_PLAYER_AUTH_EXTERNAL_TIMEOUT_MS", &mut self.external_auth.timeout_ms);
+        override_from_env("DS_PLAYER_AUTH_EXTERNAL_RETRIES", &mut self.external_auth.retries);
+        override_from_env("DS_PLAYER_AUTH_EXTERNAL_RETRY_BACKOFF_MS", &mut self.external_auth.retry_backoff_ms);
+    }
+}
+
+/// Replaces `value` with the parsed environment variable, if set and valid.
+fn override_from_env<T: FromStr>(name: &str, value: &mut T) {
+    if let Ok(raw) = std::env::var(name) {
+        match raw.parse() {
+            Ok(parsed) => *value = parsed,
+            Err(_) => warn!("🔧 DsPlayerAuthenticationPlugin: Ignoring invalid value for {}: {}", name, raw),
+        }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var(CONFIG_PATH_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    plugin_dir().map(|dir| dir.join(CONFIG_FILE_NAME))
+}
+
+/// Directory of the shared library this plugin was loaded from.
+#[cfg(unix)]
+fn plugin_dir() -> Option<PathBuf> {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    // any symbol of this library resolves to the library file
+    let symbol = plugin_dir as *const libc::c_void;
+    if unsafe { libc::dladdr(symbol, &mut info) } == 0 || info.dli_fname.is_null() {
+        return None;
+    }
+    let file = unsafe { std::ffi::CStr::from_ptr(info.dli_fname) };
+    let file = PathBuf::from(file.to_str().ok()?);
+    file.parent().map(Path::to_path_buf)
+}
+
+#[cfg(not(unix))]
+fn plugin_dir() -> Option<PathBuf> {
+    Some(PathBuf::from("plugins"))
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::config::ExternalAuthConfig;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthValidationRequest {
+    pub token: String,
+    pub game_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthValidationResponse {
+    pub valid: bool,
+    /// Identifier of the account in the account system.
+    pub player_id: Option<String>,
+    /// Name to play under, `player_id` when the service does not give one.
+    #[serde(default)]
+    pub username: Option<String>,
+    /// `Player.uuid` of the account, derived from `player_id` when the service does
+    /// not give one.
+    #[serde(default)]
+    pub player_uuid: Option<Uuid>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// Unix time in seconds until which a valid token may be trusted without asking again.
+    #[serde(default)]
+    pub expires_at: u64,
+}
+
+impl AuthValidationResponse {
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref().or(self.player_id.as_deref())
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// External authentication service client
+/// This represents integration with your existing account system
+///
+/// Each attempt is bounded by `timeout_ms` and retried after timeouts, network errors
+/// and 5xx answers. Valid tokens are cached until their `expires_at`.
+pub struct ExternalAuthService {
+    base_url: String,
+    api_key: String,
+    game_id: String,
+    retries: u32,
+    retry_backoff: Duration,
+    client: reqwest::Client,
+    /// Valid answers keyed by token.
+    cache: Mutex<HashMap<String, AuthValidationResponse>>,
+}
+
+impl ExternalAuthService {
+    pub fn new(config: &ExternalAuthConfig) -> Result<Self, String> {
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(timeout)
+            // logins run on short-lived runtimes, a pooled connection would outlive its runtime
+            .pool_max_idle_per_host(0)
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            game_id: config.game_id.clone(),
+            retries: config.retries,
+            retry_backoff: Duration::from_millis(config.retry_backoff_ms),
+            client,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Validates a player token against the external authentication service
+    /// This might be your existing OAuth provider, custom auth API, or third-party service
+    pub async fn validate_token(&self, token: &str) -> Result<AuthValidationResponse, String> {
+        let now = unix_time();
+        if let Some(cached) = self.cache.lock().unwrap().get(token).filter(|cached| cached.expires_at > now) {
+            return Ok(cached.clone());
+        }
+
+        let mut backoff = self.retry_backoff;
+        let mut attempt = 0;
+        let response = loop {
+            match self.request(token).await {
+                Ok(response) => break response,
+                Err((e, retryable)) if retryable && attempt < self.retries => {
+                    attempt += 1;
+                    debug!("Authentication service attempt {} failed, retrying in {:?}: {}", attempt, backoff, e);
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Err((e, _)) => return Err(e),
+            }
+        };
+
+        if response.valid && response.expires_at > now {
+            let mut cache = self.cache.lock().unwrap();
+            cache.retain(|_, cached| cached.expires_at > now);
+            cache.insert(token.to_string(), response.clone());
+        }
+        Ok(response)
+    }
+
+    /// One call to the service, the error tells whether it is worth retrying.
+    async fn request(&self, token: &str) -> Result<AuthValidationResponse, (String, bool)> {
+        let request = AuthValidationRequest {
+            token: token.to_string(),
+            game_id: self.game_id.clone(),
+        };
+        let response = self
+            .client
+            .post(format!("{}/auth/validate", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| (e.to_string(), true))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retryable = status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+            if !retryable {
+                warn!("🔧 DsPlayerAuthenticationPlugin: Authentication service refused the request: {}", status);
+            }
+            return Err((format!("Authentication service returned status: {}", status), retryable));
+        }
+        response.json().await.map_err(|e| (format!("invalid answer: {}", e), false))
+    }
+}
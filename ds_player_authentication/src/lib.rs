@@ -1,19 +1,21 @@
 use async_trait::async_trait;
 use horizon_event_system::{
-    AuthenticationStatusSetEvent, EventError, Event, AuthenticationStatusGetEvent, AuthenticationStatus, create_simple_plugin, EventSystem, PlayerId, current_timestamp, RawClientMessageEvent, SimplePlugin, PluginError, LogLevel, ClientConnectionRef, ServerContext
+    ClientConnectionRef, ClientEventWrapper, EventError, create_simple_plugin, EventSystem, PlayerDisconnectedEvent, PlayerId, SimplePlugin, PluginError, LogLevel, ServerContext
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-// use tokio::time::{timeout, Duration};
-use tracing::{info};
-use serde_json::json;
-use uuid::Uuid;
+use tracing::{error, info};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PlayerInit {
-    pub data: PlayerInitData,
-    pub player_id: PlayerId,
-}
+pub mod accounts;
+pub mod config;
+pub mod external;
+pub mod provider;
+pub mod session;
+pub mod stub;
+
+use crate::config::PluginConfig;
+use crate::provider::{AuthProviders, Credentials, Identity};
+use crate::session::{authenticate, Sessions};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerInitData {
@@ -21,95 +23,40 @@ pub struct PlayerInitData {
     pub password: String,
 }
 
+/// Payload of `auth`/`login`, a token issued by the account system.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PlayerSession {
-    pub username: String,
-    pub player_id: PlayerId,
-}
-
-
-
-/// External authentication service client
-/// This represents integration with your existing account system
-pub struct ExternalAuthService {
-    base_url: String,
-    api_key: String,
-    client: reqwest::Client,
-}
-
-#[derive(Serialize)]
-struct AuthValidationRequest {
-    token: String,
-    game_id: String,
+pub struct TokenLoginData {
+    pub token: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct LoginRequestEvent {
+pub struct PlayerSession {
     pub username: String,
-    pub password_hash: String,
+    pub player_id: PlayerId,
 }
 
 
-#[derive(Deserialize)]
-struct AuthValidationResponse {
-    valid: bool,
-    player_id: Option<String>,
-    permissions: Vec<String>,
-    expires_at: u64,
-}
-
-impl ExternalAuthService {
-    pub fn new(base_url: String, api_key: String) -> Self {
-        Self {
-            base_url,
-            api_key,
-            client: reqwest::Client::new(),
-        }
-    }
-
-    // /// Validates a player token against the external authentication service
-    // /// This might be your existing OAuth provider, custom auth API, or third-party service
-    // async fn validate_token(&self, token: &str) -> Result<AuthValidationResponse, Box<dyn std::error::Error + Send + Sync>> {
-    //     let request = AuthValidationRequest {
-    //         token: token.to_string(),
-    //         game_id: "DyingStar".to_string(),
-    //     };
-
-    //     let response = timeout(Duration::from_secs(5), 
-    //         self.client
-    //             .post(&format!("{}/auth/validate", self.base_url))
-    //             .header("Authorization", &format!("Bearer {}", self.api_key))
-    //             .header("Content-Type", "application/json")
-    //             .json(&request)
-    //             .send()
-    //     ).await??;
-
-    //     if !response.status().is_success() {
-    //         return Err(format!("Authentication service returned status: {}", response.status()).into());
-    //     }
-
-    //     let auth_response: AuthValidationResponse = response.json().await?;
-    //     Ok(auth_response)
-    // }
-}
 
 /// DsPlayerAuthentication Plugin
 /// Authentication plugin that handles integration with external services
 /// This design allows you to swap authentication providers without touching game logic
 pub struct DsPlayerAuthenticationPlugin {
     name: String,
+    providers: Arc<AuthProviders>,
+    sessions: Arc<Sessions>,
     // event_system: Arc<EventSystem>,
-    // auth_service: ExternalAuthService,
     // database_pool: sqlx::PgPool, // Your existing database connection    
 }
 
 impl DsPlayerAuthenticationPlugin {
     pub fn new() -> Self {
         info!("🔧 DsPlayerAuthenticationPlugin: Creating new instance");
+        let config = PluginConfig::load();
         Self {
             name: "ds_player_authentication".to_string(),
+            providers: Arc::new(AuthProviders::from_config(&config)),
+            sessions: Arc::new(Sessions::new()),
             // event_system: Arc<EventSystem>, 
-            // auth_service: ExternalAuthService{base_url: "https://toto".to_string(), api_key: "xxxx".to_string(), client: reqwest::Client::new()},
             // database_pool: sqlx::PgPool
         }
     }
@@ -131,6 +78,51 @@ impl DsPlayerAuthenticationPlugin {
 
 }
 
+/// Checks the credentials on a dedicated thread and runtime, so the handlers do not
+/// need to be inside a Tokio runtime and the slow password hashing never blocks them,
+/// then spawns the player if they are right.
+fn spawn_login(
+    events: Arc<EventSystem>,
+    providers: Arc<AuthProviders>,
+    sessions: Arc<Sessions>,
+    connection: ClientConnectionRef,
+    credentials: Credentials,
+) {
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build temp runtime");
+        rt.block_on(async move {
+            let check = providers.authenticate(&credentials);
+            if let Some(identity) = authenticate(&events, &sessions, &connection, credentials.claimed(), check).await {
+                emit_new_player(&events, &identity, connection.player_id).await;
+            }
+        });
+    });
+}
+
+/// Spawns the props player of an authenticated player. `uuid` is the permanent
+/// `Player.uuid` of the account, `internal_uuid` the `PlayerId` of this connection only.
+async fn emit_new_player(events: &EventSystem, identity: &Identity, player_id: PlayerId) {
+    if let Err(e) = events
+        .emit_plugin("propsplugin", "new_player", &serde_json::json!({
+            "username": identity.username,
+            "uuid": identity.player_uuid.to_string(),
+            "internal_uuid": player_id.to_string()
+        }))
+        .await
+    {
+        error!("Failed to emit plugin event to propsplugin: {}", e);
+    }
+}
+
+impl Default for DsPlayerAuthenticationPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 
 
 #[async_trait]
@@ -151,39 +143,36 @@ impl SimplePlugin for DsPlayerAuthenticationPlugin {
     //     let event_system = events.clone();
     //     // let db_pool = self.database_pool.clone();
     //     // TODO: Register your event handlers here
+        // each connection goes Authenticating, then Authenticated or AuthenticationFailed,
+        // and is told the outcome with a login_result before its player is spawned
         let events_system = events.clone();
-        events.on_client("player", "init", move |event: PlayerInit| {
-            println!("plugin auth: Receive player init message {:?}", event);
-
-            let events_system = events_system.clone();
-            let event = event.clone();
-
-            // Spawn a dedicated thread and runtime for the emit so we don't require
-            // the current thread to be inside a Tokio runtime.
-            std::thread::spawn(move || {
-                let rt = tokio::runtime::Builder::new_current_thread()
-                    .enable_all()
-                    .build()
-                    .expect("failed to build temp runtime");
-
-                rt.block_on(async move {
-                    if let Err(e) = events_system
-                        .emit_plugin("propsplugin", "new_player", &serde_json::json!({
-                            "username": event.data.login,
-                            "uuid": Uuid::new_v4().to_string(),
-                            "internal_uuid": event.player_id.to_string()
-                        }))
-                        .await
-                    {
-                        tracing::error!("Failed to emit plugin event to propsplugin: {}", e);
-                    }
-                });
-            });
-
-            // on_client expects a synchronous Result<(), EventError>
+        let providers = self.providers.clone();
+        let sessions = self.sessions.clone();
+        events.on_client_with_connection("player", "init", move |wrapper: ClientEventWrapper<PlayerInitData>, connection| {
+            info!("🔧 DsPlayerAuthenticationPlugin: Player {} logging in as {}", wrapper.player_id, wrapper.data.login);
+            let PlayerInitData { login, password } = wrapper.data;
+            let credentials = Credentials::Password { login, password };
+            spawn_login(events_system.clone(), providers.clone(), sessions.clone(), connection, credentials);
             Ok::<(), EventError>(())
         }).await.unwrap();
 
+        // token issued by the account system, the player is named after its account
+        let events_system = events.clone();
+        let providers = self.providers.clone();
+        let sessions = self.sessions.clone();
+        events.on_client_with_connection("auth", "login", move |wrapper: ClientEventWrapper<TokenLoginData>, connection| {
+            info!("🔧 DsPlayerAuthenticationPlugin: Player {} logging in with a token", wrapper.player_id);
+            let credentials = Credentials::Token(wrapper.data.token);
+            spawn_login(events_system.clone(), providers.clone(), sessions.clone(), connection, credentials);
+            Ok::<(), EventError>(())
+        }).await.unwrap();
+
+        let sessions = self.sessions.clone();
+        events.on_core("player_disconnected", move |event: PlayerDisconnectedEvent| {
+            sessions.forget(event.player_id);
+            Ok(())
+        }).await.unwrap();
+
             // async move {
             //     // events_system
             //     //     .emit_plugin("propsplugin", "new_player", &PlayerSession {
@@ -0,0 +1,221 @@
+use async_trait::async_trait;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::accounts::{valid_login, AccountStore, LoginError};
+use crate::config::{PluginConfig, ProviderKind};
+use crate::external::ExternalAuthService;
+
+/// What a client logs in with.
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    /// `player`/`init`.
+    Password { login: String, password: String },
+    /// `auth`/`login`, a token issued by the account system.
+    Token(String),
+}
+
+impl Credentials {
+    /// Name the client claims to be, empty for a token.
+    pub fn claimed(&self) -> &str {
+        match self {
+            Credentials::Password { login, .. } => login,
+            Credentials::Token(_) => "",
+        }
+    }
+}
+
+/// Namespace of the player UUIDs derived from an account name.
+const PLAYER_UUID_NAMESPACE: Uuid = Uuid::from_u128(0x5d1c_3e0a_8f47_4b2e_9c61_0d7a_e4b8_2f93);
+
+/// Who a player is once authenticated.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    /// Name to play under.
+    pub username: String,
+    /// `Player.uuid`, the same on every login of the account so its state survives a
+    /// reconnection. The Horizon `PlayerId` only lasts as long as the connection.
+    pub player_uuid: Uuid,
+}
+
+/// Stable `Player.uuid` of an account that has none stored, from the provider and
+/// the account name.
+pub fn derived_player_uuid(provider: &str, account: &str) -> Uuid {
+    Uuid::new_v5(&PLAYER_UUID_NAMESPACE, format!("{}:{}", provider, account).as_bytes())
+}
+
+/// A backend checking credentials, so the source of the accounts can change without
+/// touching the game logic.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether this provider handles this kind of credentials.
+    fn accepts(&self, credentials: &Credentials) -> bool;
+
+    /// Runs on the dedicated thread of the login, so it may block.
+    async fn authenticate(&self, credentials: &Credentials) -> Result<Identity, LoginError>;
+}
+
+/// Local accounts file, for passwords.
+#[async_trait]
+impl AuthProvider for AccountStore {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn accepts(&self, credentials: &Credentials) -> bool {
+        matches!(credentials, Credentials::Password { .. })
+    }
+
+    async fn authenticate(&self, credentials: &Credentials) -> Result<Identity, LoginError> {
+        let Credentials::Password { login, password } = credentials else {
+            return Err(LoginError::Unsupported);
+        };
+        let player_uuid = self.login(login, password)?;
+        Ok(Identity {
+            username: login.clone(),
+            player_uuid,
+        })
+    }
+}
+
+/// Account system over HTTP, for tokens.
+#[async_trait]
+impl AuthProvider for ExternalAuthService {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn accepts(&self, credentials: &Credentials) -> bool {
+        matches!(credentials, Credentials::Token(_))
+    }
+
+    async fn authenticate(&self, credentials: &Credentials) -> Result<Identity, LoginError> {
+        let Credentials::Token(token) = credentials else {
+            return Err(LoginError::Unsupported);
+        };
+        let response = self.validate_token(token).await.map_err(LoginError::Unavailable)?;
+        match response.username() {
+            Some(username) if response.valid => {
+                let account = response.player_id.as_deref().unwrap_or(username);
+                Ok(Identity {
+                    username: username.to_string(),
+                    player_uuid: response.player_uuid.unwrap_or_else(|| derived_player_uuid(self.name(), account)),
+                })
+            }
+            _ => Err(LoginError::BadCredentials),
+        }
+    }
+}
+
+/// Insecure development mode: any password is accepted, and a token is taken as
+/// the name to play under.
+pub struct DevProvider;
+
+#[async_trait]
+impl AuthProvider for DevProvider {
+    fn name(&self) -> &'static str {
+        "dev"
+    }
+
+    fn accepts(&self, _credentials: &Credentials) -> bool {
+        true
+    }
+
+    async fn authenticate(&self, credentials: &Credentials) -> Result<Identity, LoginError> {
+        let username = match credentials {
+            Credentials::Password { login, .. } => login,
+            Credentials::Token(token) => token,
+        };
+        if !valid_login(username) {
+            return Err(LoginError::InvalidLogin);
+        }
+        Ok(Identity {
+            username: username.clone(),
+            player_uuid: derived_player_uuid(self.name(), username),
+        })
+    }
+}
+
+/// A configured provider that failed to start, its logins are unavailable rather than
+/// handed to the next provider.
+struct Unstarted {
+    name: &'static str,
+    kind: ProviderKind,
+    reason: String,
+}
+
+#[async_trait]
+impl AuthProvider for Unstarted {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn accepts(&self, credentials: &Credentials) -> bool {
+        match self.kind {
+            ProviderKind::Local => matches!(credentials, Credentials::Password { .. }),
+            ProviderKind::Http => matches!(credentials, Credentials::Token(_)),
+            ProviderKind::Dev => true,
+        }
+    }
+
+    async fn authenticate(&self, _credentials: &Credentials) -> Result<Identity, LoginError> {
+        Err(LoginError::Unavailable(self.reason.clone()))
+    }
+}
+
+/// The providers of `[auth] providers`, a login goes to the first one accepting its
+/// kind of credentials.
+pub struct AuthProviders {
+    providers: Vec<Box<dyn AuthProvider>>,
+}
+
+impl AuthProviders {
+    pub fn from_config(config: &PluginConfig) -> Self {
+        let mut providers: Vec<Box<dyn AuthProvider>> = Vec::new();
+        for kind in &config.auth.providers {
+            let provider: Box<dyn AuthProvider> = match kind {
+                ProviderKind::Local => match AccountStore::open(&config.accounts) {
+                    Ok(accounts) => Box::new(accounts),
+                    Err(e) => {
+                        error!("🔧 DsPlayerAuthenticationPlugin: Refusing every password login, cannot load the accounts: {}", e);
+                        Box::new(Unstarted { name: "local", kind: *kind, reason: e })
+                    }
+                },
+                ProviderKind::Http => match config.external_auth.base_url.as_str() {
+                    "" => {
+                        info!("🔧 DsPlayerAuthenticationPlugin: No [external_auth] base_url, token logins disabled");
+                        continue;
+                    }
+                    base_url => match ExternalAuthService::new(&config.external_auth) {
+                        Ok(service) => {
+                            info!("🔧 DsPlayerAuthenticationPlugin: Validating login tokens with {}", base_url);
+                            Box::new(service)
+                        }
+                        Err(e) => {
+                            error!("🔧 DsPlayerAuthenticationPlugin: Refusing every token login: {}", e);
+                            Box::new(Unstarted { name: "http", kind: *kind, reason: e })
+                        }
+                    },
+                },
+                ProviderKind::Dev => {
+                    warn!("🔧 DsPlayerAuthenticationPlugin: ⚠️ INSECURE dev authentication, any login is accepted");
+                    Box::new(DevProvider)
+                }
+            };
+            providers.push(provider);
+        }
+        Self { providers }
+    }
+
+    pub async fn authenticate(&self, credentials: &Credentials) -> Result<Identity, LoginError> {
+        match self.providers.iter().find(|provider| provider.accepts(credentials)) {
+            Some(provider) => {
+                debug!("Login of {:?} checked by the {} provider", credentials.claimed(), provider.name());
+                provider.authenticate(credentials).await
+            }
+            None => Err(LoginError::Unsupported),
+        }
+    }
+}
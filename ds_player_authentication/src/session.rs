@@ -0,0 +1,252 @@
+use horizon_event_system::{
+    current_timestamp, AuthenticationStatus, AuthenticationStatusSetEvent, ClientConnectionRef, EventSystem, PlayerId,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::accounts::LoginError;
+use crate::provider::Identity;
+
+/// Why a login failed, as told to the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginFailure {
+    InvalidLogin,
+    BadCredentials,
+    /// The accounts cannot be checked right now, the client may retry later.
+    Unavailable,
+    /// This connection already logged in, or is logging in.
+    AlreadyAuthenticated,
+    /// This server does not accept this way of logging in.
+    Unsupported,
+    /// The account is already playing on another connection.
+    AccountInUse,
+}
+
+impl From<&LoginError> for LoginFailure {
+    fn from(error: &LoginError) -> Self {
+        match error {
+            LoginError::InvalidLogin => LoginFailure::InvalidLogin,
+            LoginError::BadCredentials => LoginFailure::BadCredentials,
+            // the reason stays in the server logs
+            LoginError::Unavailable(_) => LoginFailure::Unavailable,
+            LoginError::Unsupported => LoginFailure::Unsupported,
+        }
+    }
+}
+
+/// Answer to `player`/`init`, sent to that client only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "login_result")]
+pub struct LoginResult {
+    pub success: bool,
+    pub login: String,
+    /// `Player.uuid` of the account, on success.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_uuid: Option<Uuid>,
+    pub status: AuthenticationStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<LoginFailure>,
+}
+
+impl LoginResult {
+    pub fn accepted(identity: &Identity) -> Self {
+        Self {
+            success: true,
+            login: identity.username.clone(),
+            player_uuid: Some(identity.player_uuid),
+            status: AuthenticationStatus::Authenticated,
+            error: None,
+        }
+    }
+
+    pub fn refused(login: &str, status: AuthenticationStatus, error: LoginFailure) -> Self {
+        Self {
+            success: false,
+            login: login.to_string(),
+            player_uuid: None,
+            status,
+            error: Some(error),
+        }
+    }
+}
+
+/// Why an account cannot be given to a connection.
+enum ClaimError {
+    /// The connection closed while its login was checked.
+    Disconnected,
+    /// Another connection is playing the account.
+    InUse(PlayerId),
+}
+
+/// Authentication status of the connected players, as last sent to Horizon with
+/// `auth_status_set`.
+///
+/// Each login attempt gets a generation, so a check finishing after its player
+/// disconnected, or after a newer attempt began, changes nothing.
+pub struct Sessions {
+    statuses: Mutex<HashMap<PlayerId, (AuthenticationStatus, u64)>>,
+    next_generation: AtomicU64,
+    /// Connection playing each account, keyed by `Player.uuid`.
+    players: Mutex<HashMap<Uuid, PlayerId>>,
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self {
+            statuses: Mutex::new(HashMap::new()),
+            next_generation: AtomicU64::new(1),
+            players: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Gives an account to a connection, unless another one is playing it or the
+    /// login attempt is no longer current.
+    fn claim(&self, player_id: PlayerId, generation: u64, player_uuid: Uuid) -> Result<(), ClaimError> {
+        // held while claiming, so `forget` cannot run in between
+        let statuses = self.statuses.lock().unwrap();
+        if !matches!(statuses.get(&player_id), Some((_, current)) if *current == generation) {
+            return Err(ClaimError::Disconnected);
+        }
+        let mut players = self.players.lock().unwrap();
+        match players.get(&player_uuid) {
+            Some(owner) if *owner != player_id => Err(ClaimError::InUse(*owner)),
+            _ => {
+                players.insert(player_uuid, player_id);
+                Ok(())
+            }
+        }
+    }
+
+    /// Moves a player to `Authenticating` and returns the generation of this login
+    /// attempt, or returns its status if it is already authenticating or
+    /// authenticated. A failed login may be retried.
+    pub fn begin(&self, player_id: PlayerId) -> Result<u64, AuthenticationStatus> {
+        let mut statuses = self.statuses.lock().unwrap();
+        match statuses.get(&player_id) {
+            Some((status @ (AuthenticationStatus::Authenticating | AuthenticationStatus::Authenticated), _)) => Err(*status),
+            _ => {
+                let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+                statuses.insert(player_id, (AuthenticationStatus::Authenticating, generation));
+                Ok(generation)
+            }
+        }
+    }
+
+    /// Records the status of a player and tells Horizon about it. Returns false, and
+    /// does nothing, if the login attempt `generation` is no longer current.
+    pub async fn set(&self, events: &EventSystem, player_id: PlayerId, generation: u64, status: AuthenticationStatus) -> bool {
+        match self.statuses.lock().unwrap().get_mut(&player_id) {
+            Some((current, current_generation)) if *current_generation == generation => *current = status,
+            _ => return false,
+        }
+        let event = AuthenticationStatusSetEvent {
+            player_id,
+            status,
+            timestamp: current_timestamp(),
+        };
+        if let Err(e) = events.emit_core("auth_status_set", &event).await {
+            error!("Failed to set authentication status of player {} to {:?}: {}", player_id, status, e);
+        }
+        true
+    }
+
+    /// Drops a disconnected player and releases its account. A login still being
+    /// checked is abandoned.
+    pub fn forget(&self, player_id: PlayerId) {
+        let mut statuses = self.statuses.lock().unwrap();
+        statuses.remove(&player_id);
+        self.players.lock().unwrap().retain(|_, owner| *owner != player_id);
+    }
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Takes a connection through `Authenticating`, then `Authenticated` or
+/// `AuthenticationFailed` depending on `check`, and sends the client its
+/// `login_result`. `claimed` is the name the client gave, if any. Returns who the
+/// player is when the login succeeded, never for a player who disconnected meanwhile.
+pub async fn authenticate<F>(
+    events: &EventSystem,
+    sessions: &Sessions,
+    connection: &ClientConnectionRef,
+    claimed: &str,
+    check: F,
+) -> Option<Identity>
+where
+    F: Future<Output = Result<Identity, LoginError>>,
+{
+    let player_id = connection.player_id;
+    let generation = match sessions.begin(player_id) {
+        Ok(generation) => generation,
+        Err(status) => {
+            warn!("🔧 DsPlayerAuthenticationPlugin: Player {} tried to log in again while {:?}", player_id, status);
+            respond(connection, &LoginResult::refused(claimed, status, LoginFailure::AlreadyAuthenticated)).await;
+            return None;
+        }
+    };
+    if !sessions.set(events, player_id, generation, AuthenticationStatus::Authenticating).await {
+        return abandoned(player_id, claimed);
+    }
+
+    let verdict = check.await;
+    if let Ok(identity) = &verdict {
+        match sessions.claim(player_id, generation, identity.player_uuid) {
+            Ok(()) => {}
+            Err(ClaimError::Disconnected) => return abandoned(player_id, &identity.username),
+            Err(ClaimError::InUse(owner)) => {
+                if !sessions.set(events, player_id, generation, AuthenticationStatus::AuthenticationFailed).await {
+                    return abandoned(player_id, &identity.username);
+                }
+                warn!(
+                    "🔧 DsPlayerAuthenticationPlugin: Refused login of player {} as {}, already played by {}",
+                    player_id, identity.username, owner
+                );
+                let result = LoginResult::refused(&identity.username, AuthenticationStatus::AuthenticationFailed, LoginFailure::AccountInUse);
+                respond(connection, &result).await;
+                return None;
+            }
+        }
+    }
+    match verdict {
+        Ok(identity) => {
+            // a disconnection right after the claim already released the account
+            if !sessions.set(events, player_id, generation, AuthenticationStatus::Authenticated).await {
+                return abandoned(player_id, &identity.username);
+            }
+            info!("🔧 DsPlayerAuthenticationPlugin: Player {} authenticated as {}", player_id, identity.username);
+            respond(connection, &LoginResult::accepted(&identity)).await;
+            Some(identity)
+        }
+        Err(e) => {
+            if !sessions.set(events, player_id, generation, AuthenticationStatus::AuthenticationFailed).await {
+                return abandoned(player_id, claimed);
+            }
+            warn!("🔧 DsPlayerAuthenticationPlugin: Refused login of player {} as {}: {}", player_id, claimed, e);
+            let failure = LoginFailure::from(&e);
+            respond(connection, &LoginResult::refused(claimed, AuthenticationStatus::AuthenticationFailed, failure)).await;
+            None
+        }
+    }
+}
+
+/// Ends a login attempt whose player disconnected while it was checked.
+fn abandoned(player_id: PlayerId, login: &str) -> Option<Identity> {
+    info!("🔧 DsPlayerAuthenticationPlugin: Player {} disconnected before its login as {} completed", player_id, login);
+    None
+}
+
+async fn respond(connection: &ClientConnectionRef, result: &LoginResult) {
+    if let Err(e) = connection.respond_json(result).await {
+        error!("Failed to send login result to player {}: {}", connection.player_id, e);
+    }
+}
@@ -0,0 +1,165 @@
+//! Stub of the account system, served by the `stub_account_service` binary for local
+//! development and by the tests of [`ExternalAuthService`](crate::external::ExternalAuthService).
+//!
+//! Answers `POST /auth/validate` like the account service `ExternalAuthService`
+//! expects, for the tokens it knows. It can also fail or slow down on purpose to
+//! exercise the retries and timeouts of the plugin.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::external::{AuthValidationRequest, AuthValidationResponse};
+
+/// Largest request accepted, headers included.
+const MAX_REQUEST_LEN: usize = 64 * 1024;
+
+/// Misbehaviour of the stub on one request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Answers with this status instead, 503 for an outage or 429 for throttling.
+    Status(u16),
+    /// Answers normally, but only after this delay.
+    Delay(Duration),
+}
+
+pub struct StubAccountService {
+    /// Expected as `Authorization: Bearer`, anything else is answered 401.
+    pub api_key: String,
+    /// Username of each valid token.
+    pub tokens: HashMap<String, String>,
+    /// Lifetime of the validations, given as `expires_at`.
+    pub ttl_secs: u64,
+    /// Wait before every answer.
+    pub delay: Duration,
+    /// Played in order on the first requests, before the stub works normally.
+    pub faults: Mutex<VecDeque<Fault>>,
+    /// Requests received so far.
+    pub requests: AtomicUsize,
+}
+
+impl Default for StubAccountService {
+    fn default() -> Self {
+        Self {
+            api_key: String::new(),
+            tokens: HashMap::new(),
+            ttl_secs: 300,
+            delay: Duration::ZERO,
+            faults: Mutex::new(VecDeque::new()),
+            requests: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl StubAccountService {
+    /// Serves the connections of `listener` until the task is dropped, logging each
+    /// request on stdout.
+    pub async fn run(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            if let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(self.clone().serve(stream));
+            }
+        }
+    }
+
+    async fn serve(self: Arc<Self>, mut stream: TcpStream) {
+        let Some((head, body)) = read_request(&mut stream).await else {
+            return;
+        };
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        let fault = self.faults.lock().unwrap().pop_front();
+        let mut delay = self.delay;
+        if let Some(Fault::Delay(extra)) = fault {
+            delay += extra;
+        }
+        tokio::time::sleep(delay).await;
+        let (status, body) = match fault {
+            Some(Fault::Status(status)) => (status, String::new()),
+            _ => self.answer(&head, &body),
+        };
+        println!("{} -> {}", head.lines().next().unwrap_or_default(), status);
+        let reason = match status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            429 => "Too Many Requests",
+            _ => "Service Unavailable",
+        };
+        let response = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            reason,
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+
+    fn answer(&self, head: &str, body: &str) -> (u16, String) {
+        if !head.starts_with("POST /auth/validate ") {
+            return (404, String::new());
+        }
+        let authorized = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .any(|(name, value)| name.trim().eq_ignore_ascii_case("authorization") && value.trim() == format!("Bearer {}", self.api_key));
+        if !authorized {
+            return (401, String::new());
+        }
+        let Ok(request) = serde_json::from_str::<AuthValidationRequest>(body) else {
+            return (400, String::new());
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let response = match self.tokens.get(&request.token) {
+            Some(username) => AuthValidationResponse {
+                valid: true,
+                player_id: Some(format!("account-{}", username)),
+                username: Some(username.clone()),
+                player_uuid: None,
+                permissions: vec!["play".to_string()],
+                expires_at: now + self.ttl_secs,
+            },
+            None => AuthValidationResponse {
+                valid: false,
+                player_id: None,
+                username: None,
+                player_uuid: None,
+                permissions: Vec::new(),
+                expires_at: 0,
+            },
+        };
+        (200, serde_json::to_string(&response).unwrap_or_default())
+    }
+}
+
+/// Reads a request up to the end of its body, as told by `Content-Length`.
+async fn read_request(stream: &mut TcpStream) -> Option<(String, String)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 || buffer.len() + read > MAX_REQUEST_LEN {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") else {
+            continue;
+        };
+        let head = String::from_utf8_lossy(&buffer[..end]).to_string();
+        let length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        if buffer.len() >= end + 4 + length {
+            let body = String::from_utf8_lossy(&buffer[end + 4..end + 4 + length]).to_string();
+            return Some((head, body));
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Local accounts file: password checks, legacy accounts and reloading.
+
+use plugin_ds_player_authentication::accounts::{hash_password, AccountStore, LoginError};
+use plugin_ds_player_authentication::config::AccountsConfig;
+use serde_json::json;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Directory of a test, removed when dropped.
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("ds_accounts_{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        Self(dir)
+    }
+
+    fn config(&self, register_unknown: bool) -> AccountsConfig {
+        AccountsConfig {
+            path: self.0.join("accounts.json").to_string_lossy().into_owned(),
+            register_unknown,
+        }
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn login_checks_the_password() {
+    let dir = TestDir::new();
+    let store = AccountStore::open(&dir.config(false)).unwrap();
+    store.set_password("alice", "secret").unwrap();
+
+    let player_uuid = store.login("alice", "secret").unwrap();
+    assert_eq!(store.login("alice", "secret"), Ok(player_uuid));
+    assert_eq!(store.login("alice", "wrong"), Err(LoginError::BadCredentials));
+    assert_eq!(store.login("bob", "secret"), Err(LoginError::BadCredentials));
+    assert_eq!(store.login("alice bob", "secret"), Err(LoginError::InvalidLogin));
+
+    // a new password keeps the account
+    store.set_password("alice", "other").unwrap();
+    assert_eq!(store.login("alice", "other"), Ok(player_uuid));
+}
+
+#[test]
+fn unknown_login_is_registered_when_allowed() {
+    let dir = TestDir::new();
+    let store = AccountStore::open(&dir.config(true)).unwrap();
+
+    let player_uuid = store.login("alice", "secret").unwrap();
+    assert_eq!(store.login("alice", "secret"), Ok(player_uuid));
+    assert_eq!(store.login("alice", "wrong"), Err(LoginError::BadCredentials));
+    assert_eq!(store.login("bob", ""), Err(LoginError::BadCredentials));
+}
+
+#[test]
+fn concurrent_first_logins_register_once() {
+    let dir = TestDir::new();
+    let store = AccountStore::open(&dir.config(true)).unwrap();
+
+    let logins: Vec<_> = std::thread::scope(|scope| {
+        let first = scope.spawn(|| store.login("alice", "first"));
+        let second = scope.spawn(|| store.login("alice", "second"));
+        vec![first.join().unwrap(), second.join().unwrap()]
+    });
+    let registered: Vec<_> = logins.iter().filter(|login| login.is_ok()).collect();
+    assert_eq!(registered.len(), 1, "{:?}", logins);
+    assert!(logins.contains(&Err(LoginError::BadCredentials)));
+
+    // the password is the one of the login that succeeded
+    let password = if logins[0].is_ok() { "first" } else { "second" };
+    assert_eq!(&store.login("alice", password), registered[0]);
+}
+
+#[test]
+fn legacy_account_gets_a_lasting_player_uuid() {
+    let dir = TestDir::new();
+    let config = dir.config(false);
+    let legacy = json!({
+        "accounts": {
+            "alice": { "password_hash": hash_password("secret").unwrap(), "created_at": "2025-01-01T00:00:00Z" }
+        }
+    });
+    std::fs::write(&config.path, legacy.to_string()).unwrap();
+
+    let player_uuid = AccountStore::open(&config).unwrap().login("alice", "secret").unwrap();
+    let reopened = AccountStore::open(&config).unwrap();
+    assert_eq!(reopened.accounts().unwrap()["alice"].player_uuid, Some(player_uuid));
+    assert_eq!(reopened.login("alice", "secret"), Ok(player_uuid));
+}
+
+#[test]
+fn accounts_changed_by_another_process_are_reloaded() {
+    let dir = TestDir::new();
+    let config = dir.config(false);
+    let store = AccountStore::open(&config).unwrap();
+    assert_eq!(store.login("alice", "secret"), Err(LoginError::BadCredentials));
+
+    // as done by `ds_accounts` while the server runs
+    AccountStore::open(&config).unwrap().set_password("alice", "secret").unwrap();
+    assert!(store.login("alice", "secret").is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn accounts_file_is_only_readable_by_its_owner() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TestDir::new();
+    let config = dir.config(false);
+    AccountStore::open(&config).unwrap().set_password("alice", "secret").unwrap();
+    let mode = std::fs::metadata(&config.path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
@@ -0,0 +1,92 @@
+//! Token validation against the stub account system.
+
+use plugin_ds_player_authentication::config::ExternalAuthConfig;
+use plugin_ds_player_authentication::external::ExternalAuthService;
+use plugin_ds_player_authentication::stub::{Fault, StubAccountService};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const API_KEY: &str = "test-key";
+
+/// Starts a stub knowing the token `alice`, playing `faults` on the first requests.
+async fn account_service(faults: Vec<Fault>, ttl_secs: u64) -> (String, Arc<StubAccountService>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let stub = Arc::new(StubAccountService {
+        api_key: API_KEY.to_string(),
+        tokens: [("alice".to_string(), "alice".to_string())].into(),
+        ttl_secs,
+        faults: Mutex::new(faults.into()),
+        ..StubAccountService::default()
+    });
+    tokio::spawn(stub.clone().run(listener));
+    (base_url, stub)
+}
+
+fn service(base_url: String, api_key: &str) -> ExternalAuthService {
+    ExternalAuthService::new(&ExternalAuthConfig {
+        base_url,
+        api_key: api_key.to_string(),
+        timeout_ms: 200,
+        retries: 2,
+        retry_backoff_ms: 10,
+        ..ExternalAuthConfig::default()
+    })
+    .unwrap()
+}
+
+fn requests(stub: &StubAccountService) -> usize {
+    stub.requests.load(Ordering::SeqCst)
+}
+
+#[tokio::test]
+async fn server_errors_and_throttling_are_retried() {
+    let (base_url, stub) = account_service(vec![Fault::Status(503), Fault::Status(429)], 60).await;
+    let response = service(base_url, API_KEY).validate_token("alice").await.unwrap();
+    assert!(response.valid);
+    assert_eq!(response.username(), Some("alice"));
+    assert_eq!(requests(&stub), 3);
+}
+
+#[tokio::test]
+async fn timeouts_are_retried() {
+    let (base_url, stub) = account_service(vec![Fault::Delay(Duration::from_secs(2))], 60).await;
+    assert!(service(base_url, API_KEY).validate_token("alice").await.unwrap().valid);
+    assert_eq!(requests(&stub), 2);
+}
+
+#[tokio::test]
+async fn retries_are_bounded() {
+    let (base_url, stub) = account_service(vec![Fault::Status(503); 5], 60).await;
+    let error = service(base_url, API_KEY).validate_token("alice").await.unwrap_err();
+    assert!(error.contains("503"), "{}", error);
+    assert_eq!(requests(&stub), 3);
+}
+
+#[tokio::test]
+async fn client_errors_are_not_retried() {
+    let (base_url, stub) = account_service(Vec::new(), 60).await;
+    let error = service(base_url, "wrong-key").validate_token("alice").await.unwrap_err();
+    assert!(error.contains("401"), "{}", error);
+    assert_eq!(requests(&stub), 1);
+}
+
+#[tokio::test]
+async fn valid_tokens_are_cached_until_they_expire() {
+    let (base_url, stub) = account_service(Vec::new(), 2).await;
+    let service = service(base_url, API_KEY);
+    let response = service.validate_token("alice").await.unwrap();
+    assert!(service.validate_token("alice").await.unwrap().valid);
+    assert_eq!(requests(&stub), 1);
+    // an unknown token is neither valid nor cached
+    assert!(!service.validate_token("bob").await.unwrap().valid);
+    assert!(!service.validate_token("bob").await.unwrap().valid);
+    assert_eq!(requests(&stub), 3);
+
+    let expired = std::time::UNIX_EPOCH + Duration::from_secs(response.expires_at + 1);
+    tokio::time::sleep(expired.duration_since(std::time::SystemTime::now()).unwrap()).await;
+    service.validate_token("alice").await.unwrap();
+    assert_eq!(requests(&stub), 4);
+}
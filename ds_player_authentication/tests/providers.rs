@@ -0,0 +1,84 @@
+//! Choice of the provider checking a login, from `[auth] providers`.
+
+use plugin_ds_player_authentication::accounts::LoginError;
+use plugin_ds_player_authentication::config::{PluginConfig, ProviderKind};
+use plugin_ds_player_authentication::provider::{derived_player_uuid, AuthProviders, Credentials};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Accounts file of a test, removed when dropped.
+struct AccountsFile(PathBuf);
+
+impl AccountsFile {
+    fn new(content: Option<&str>) -> Self {
+        let path = std::env::temp_dir().join(format!("ds_providers_{}.json", Uuid::new_v4()));
+        if let Some(content) = content {
+            std::fs::write(&path, content).unwrap();
+        }
+        Self(path)
+    }
+}
+
+impl Drop for AccountsFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn providers(kinds: &[ProviderKind], accounts: &AccountsFile) -> AuthProviders {
+    let mut config = PluginConfig::default();
+    config.auth.providers = kinds.to_vec();
+    config.accounts.path = accounts.0.to_string_lossy().into_owned();
+    // a token login would reach this address if the http provider were picked
+    config.external_auth.base_url = "http://127.0.0.1:1".to_string();
+    config.external_auth.timeout_ms = 200;
+    config.external_auth.retries = 0;
+    AuthProviders::from_config(&config)
+}
+
+fn password(login: &str, password: &str) -> Credentials {
+    Credentials::Password {
+        login: login.to_string(),
+        password: password.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn logins_go_to_the_first_provider_of_their_kind() {
+    let accounts = AccountsFile::new(None);
+    let providers = providers(&[ProviderKind::Local, ProviderKind::Http, ProviderKind::Dev], &accounts);
+
+    // the local accounts refuse an unknown login, the dev provider is never asked
+    assert_eq!(providers.authenticate(&password("alice", "secret")).await.unwrap_err(), LoginError::BadCredentials);
+    // a token goes to the account service, which is down
+    let error = providers.authenticate(&Credentials::Token("alice".to_string())).await.unwrap_err();
+    assert!(matches!(error, LoginError::Unavailable(_)), "{:?}", error);
+}
+
+#[tokio::test]
+async fn dev_provider_accepts_any_login() {
+    let accounts = AccountsFile::new(None);
+    let providers = providers(&[ProviderKind::Http, ProviderKind::Dev], &accounts);
+
+    let identity = providers.authenticate(&password("alice", "anything")).await.unwrap();
+    assert_eq!(identity.username, "alice");
+    assert_eq!(identity.player_uuid, derived_player_uuid("dev", "alice"));
+    let error = providers.authenticate(&password("not valid", "anything")).await.unwrap_err();
+    assert_eq!(error, LoginError::InvalidLogin);
+}
+
+#[tokio::test]
+async fn credentials_without_provider_are_unsupported() {
+    let accounts = AccountsFile::new(None);
+    let providers = providers(&[ProviderKind::Local], &accounts);
+    let error = providers.authenticate(&Credentials::Token("alice".to_string())).await.unwrap_err();
+    assert_eq!(error, LoginError::Unsupported);
+}
+
+#[tokio::test]
+async fn broken_provider_does_not_fall_back_to_the_next() {
+    let accounts = AccountsFile::new(Some("not json"));
+    let providers = providers(&[ProviderKind::Local, ProviderKind::Dev], &accounts);
+    let error = providers.authenticate(&password("alice", "secret")).await.unwrap_err();
+    assert!(matches!(error, LoginError::Unavailable(_)), "{:?}", error);
+}
@@ -0,0 +1,122 @@
+//! Login flow of a connection, without a Horizon server.
+
+use horizon_event_system::{AuthenticationStatus, ClientConnectionRef, ClientResponseSender, EventSystem, PlayerId};
+use plugin_ds_player_authentication::accounts::LoginError;
+use plugin_ds_player_authentication::provider::Identity;
+use plugin_ds_player_authentication::session::{authenticate, LoginFailure, LoginResult, Sessions};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// Keeps the `login_result` sent to the client.
+#[derive(Debug, Default)]
+struct Client {
+    results: Mutex<Vec<LoginResult>>,
+}
+
+impl Client {
+    fn results(&self) -> Vec<LoginResult> {
+        self.results.lock().unwrap().clone()
+    }
+}
+
+impl ClientResponseSender for Client {
+    fn send_to_client(&self, _player_id: PlayerId, data: Vec<u8>) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send + '_>> {
+        self.results.lock().unwrap().push(serde_json::from_slice(&data).unwrap());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn is_connection_active(&self, _player_id: PlayerId) -> Pin<Box<dyn Future<Output = bool> + Send + '_>> {
+        Box::pin(async { true })
+    }
+
+    fn get_auth_status(&self, _player_id: PlayerId) -> Pin<Box<dyn Future<Output = Option<AuthenticationStatus>> + Send + '_>> {
+        Box::pin(async { None })
+    }
+
+    fn kick(&self, _player_id: PlayerId, _reason: Option<String>) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send + '_>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+fn connect() -> (ClientConnectionRef, Arc<Client>) {
+    let client = Arc::new(Client::default());
+    let connection = ClientConnectionRef::new(
+        PlayerId::new(),
+        "127.0.0.1:4000".parse().unwrap(),
+        Uuid::new_v4().to_string(),
+        0,
+        AuthenticationStatus::Unauthenticated,
+        client.clone(),
+    );
+    (connection, client)
+}
+
+fn alice() -> Identity {
+    Identity {
+        username: "alice".to_string(),
+        player_uuid: Uuid::from_u128(1),
+    }
+}
+
+#[tokio::test]
+async fn account_is_played_by_one_connection_at_a_time() {
+    let (events, sessions) = (EventSystem::new(), Sessions::new());
+    let (first, first_client) = connect();
+    let (second, second_client) = connect();
+
+    assert!(authenticate(&events, &sessions, &first, "alice", async { Ok(alice()) }).await.is_some());
+    assert!(first_client.results()[0].success);
+    // logging in again on the same connection is refused
+    assert!(authenticate(&events, &sessions, &first, "alice", async { Ok(alice()) }).await.is_none());
+    assert_eq!(first_client.results()[1].error, Some(LoginFailure::AlreadyAuthenticated));
+
+    assert!(authenticate(&events, &sessions, &second, "alice", async { Ok(alice()) }).await.is_none());
+    assert_eq!(second_client.results()[0].error, Some(LoginFailure::AccountInUse));
+    // a failed login may be retried, and the account is free once its player left
+    sessions.forget(first.player_id);
+    assert!(authenticate(&events, &sessions, &second, "alice", async { Ok(alice()) }).await.is_some());
+}
+
+#[tokio::test]
+async fn refused_login_may_be_retried() {
+    let (events, sessions) = (EventSystem::new(), Sessions::new());
+    let (connection, client) = connect();
+
+    let refused = async { Err(LoginError::BadCredentials) };
+    assert!(authenticate(&events, &sessions, &connection, "alice", refused).await.is_none());
+    assert_eq!(client.results()[0].error, Some(LoginFailure::BadCredentials));
+    assert!(authenticate(&events, &sessions, &connection, "alice", async { Ok(alice()) }).await.is_some());
+}
+
+#[tokio::test]
+async fn disconnection_during_the_check_abandons_the_login() {
+    let (events, sessions) = (Arc::new(EventSystem::new()), Arc::new(Sessions::new()));
+    let (connection, client) = connect();
+    let player_id = connection.player_id;
+    let (started, checking) = oneshot::channel();
+    let (verdict, check) = oneshot::channel();
+
+    let login = {
+        let (events, sessions) = (events.clone(), sessions.clone());
+        tokio::spawn(async move {
+            let check = async {
+                started.send(()).unwrap();
+                check.await.unwrap()
+            };
+            authenticate(&events, &sessions, &connection, "alice", check).await
+        })
+    };
+    // the check is still running when the player leaves
+    checking.await.unwrap();
+    sessions.forget(player_id);
+    verdict.send(Ok(alice())).unwrap();
+
+    assert!(login.await.unwrap().is_none());
+    assert!(client.results().is_empty());
+    // the account was not taken by the connection that left
+    let (other, _) = connect();
+    assert!(authenticate(&events, &sessions, &other, "alice", async { Ok(alice()) }).await.is_some());
+}
@@ -1,12 +1,13 @@
 use async_trait::async_trait;
 use horizon_event_system::{
-    CompressionType, create_simple_plugin, defObject, EventSystem, PlayerId, LogLevel, PluginError, ReplicationLayer, ReplicationPriority, ServerContext, SimplePlugin, Vec3, PlayerDisconnectedEvent
+    create_simple_plugin, ClientEventWrapper, EventSystem, PlayerId, LogLevel, PluginError, ServerContext, SimplePlugin, Vec3, PlayerDisconnectedEvent
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
-use tracing::{error, info};
+use tracing::{debug, error, info};
 pub mod props;
 use crate::props::testplanet::Testplanet;
 use crate::props::player::Player;
@@ -18,6 +19,36 @@ pub struct PlayerSession {
     pub player_id: PlayerId,
 }
 
+/// Outcome of an `add_props`/`add_prop` sent to the game server, keyed by the
+/// `request_id` given with the command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnResult {
+    pub request_id: String,
+    pub command: String,
+    pub ok: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// The game server link went down before answering. Nothing to roll back, the
+    /// world snapshot sent on the reconnect spawns it again.
+    #[serde(default)]
+    pub link_lost: bool,
+}
+
+/// Action of a player accepted by ds_game_server that creates a prop.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerActionData {
+    pub player_id: PlayerId,
+    pub player_uuid: String,
+    pub action: String,
+}
+
+/// Clock synchronization asked by a client (`time`/`sync`), `t0` being its own clock
+/// when it sent the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeSyncRequest {
+    pub t0: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewPlayerData {
     pub username: String,
@@ -32,6 +63,18 @@ pub struct DyingstarPropsPlugin {
     planets: Arc<RwLock<HashMap<String, Testplanet>>>,
     // object_registry: Arc<GorcObjectRegistry>,
     players: Arc<RwLock<HashMap<PlayerId, Player>>>,
+    /// Link state of each game server region, by region name.
+    regions_up: Arc<Mutex<HashMap<String, bool>>>,
+    /// Player who asked for each prop spawn still waiting for the game server.
+    spawn_requests: Arc<RwLock<HashMap<String, PlayerId>>>,
+    /// Horizon `server_tick`s since the start, stamped on the position broadcasts.
+    ticks: Arc<AtomicU64>,
+}
+
+impl Default for DyingstarPropsPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DyingstarPropsPlugin {
@@ -43,6 +86,9 @@ impl DyingstarPropsPlugin {
             planets: Arc::new(RwLock::new(HashMap::new())),
             // object_registry: Arc::new(GorcObjectRegistry::new()),
             players: Arc::new(RwLock::new(HashMap::new())),
+            regions_up: Arc::new(Mutex::new(HashMap::new())),
+            spawn_requests: Arc::new(RwLock::new(HashMap::new())),
+            ticks: Arc::new(AtomicU64::new(0)),
         }
     }
 
@@ -125,11 +171,88 @@ impl DyingstarPropsPlugin {
             session.player_id.to_string(),
             "".to_string(),
         );
-        self.players.write().await.insert(session.player_id.clone(), player.clone());
+        self.players.write().await.insert(session.player_id, player.clone());
         player
     }
 }
 
+/// Server clock shared with the clients and the game servers, in milliseconds since
+/// the Unix epoch. Same clock as `protocol::server_time_ms` of ds_game_server.
+fn server_time_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// True unless every known game server region is down, positions received before are stale.
+fn game_server_up(regions_up: &Mutex<HashMap<String, bool>>) -> bool {
+    let regions_up = regions_up.lock().unwrap();
+    regions_up.is_empty() || regions_up.values().any(|up| *up)
+}
+
+/// Creates the server initial planets unless they exist. Returns true when it did,
+/// the game server is then initialized with them.
+async fn create_planets(planets: &RwLock<HashMap<String, Testplanet>>) -> bool {
+    let mut planets = planets.write().await;
+    if !planets.is_empty() {
+        return false;
+    }
+    // create sandbox planet and store it
+    let sandbox = Testplanet::new(
+        "Sandbox".to_string(),
+        Vec3::new(98785.898, 13339.8, -10386.2), // Vec3::new(15067000000.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 0.0),
+    );
+    planets.insert(sandbox.uuid.clone(), sandbox);
+    true
+}
+
+/// Removes the players of a connection and returns their `Player.uuid`.
+async fn remove_players(players: &RwLock<HashMap<PlayerId, Player>>, internal_uuid: &str) -> Vec<String> {
+    let mut removed = Vec::new();
+    players.write().await.retain(|_, player| {
+        if player.internal_uuid == internal_uuid {
+            removed.push(player.uuid.clone());
+        }
+        player.internal_uuid != internal_uuid
+    });
+    removed
+}
+
+/// Creates a box50cm for `player_uuid` and asks the game server to spawn it.
+async fn spawn_box50cm(
+    boxes50cm: &RwLock<HashMap<String, Box50cm>>,
+    spawn_requests: &RwLock<HashMap<String, PlayerId>>,
+    events: &EventSystem,
+    requester: Option<PlayerId>,
+    player_uuid: &str,
+) {
+    // create Box50cm and store it
+    let box50cm_id = uuid::Uuid::new_v4().to_string();
+    let box50cm = Box50cm::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 0.0),
+        box50cm_id.clone(),
+    );
+    boxes50cm.write().await.insert(box50cm_id.clone(), box50cm.clone());
+
+    // remember who asked, to report a failed spawn
+    if let Some(player_id) = requester {
+        spawn_requests.write().await.insert(box50cm_id.clone(), player_id);
+    }
+
+    let payload = serde_json::json!({
+        "box50cm": box50cm,
+        "player_uuid": player_uuid,
+        "request_id": box50cm_id,
+    });
+
+    if let Err(e) = events.emit_plugin("gameserverplugin", "add_prop", &payload).await {
+        tracing::error!("Failed to emit plugin event to propsplugin, add_prop: {}", e);
+    }
+}
+
 #[async_trait]
 impl SimplePlugin for DyingstarPropsPlugin {
     fn name(&self) -> &str {
@@ -140,7 +263,7 @@ impl SimplePlugin for DyingstarPropsPlugin {
         "1.0.0"
     }
 
-    async fn register_handlers(&mut self, events: Arc<EventSystem>, _context: Arc<dyn ServerContext>) -> Result<(), PluginError> {
+    async fn register_handlers(&mut self, events: Arc<EventSystem>, context: Arc<dyn ServerContext>) -> Result<(), PluginError> {
         info!("🔧 DyingstarPropsPlugin: Registering event handlers...");
 
         // Obtain the current Tokio runtime handle once (register_handlers runs inside runtime)
@@ -164,7 +287,6 @@ impl SimplePlugin for DyingstarPropsPlugin {
         // Make distinct clones of the runtime handle for each closure so none of them
         // takes ownership of the original `rt_handle`.
         let rt_handle_for_new_player = rt_handle.clone();
-        let rt_handle_for_position_update = rt_handle.clone();
 
         // on_plugin expects a synchronous callback returning Result<_, EventError>.
         // spawn a tokio task to perform async work inside the handler.
@@ -186,24 +308,15 @@ impl SimplePlugin for DyingstarPropsPlugin {
                 info!("🔧 DyingstarPropsPlugin: ✅ New player connected: {} ({})", event.username, event.uuid);
 
                 let mut new_players: Vec<Player> = Vec::new();
-                let mut first_player: bool = false;
-
-                // if players list is empty -> create server initial planets inline (avoid calling self)
-                if players.read().await.len() == 0 {
-                    first_player = true;
-                    // create sandbox planet and store it
-                    let sandbox = Testplanet::new(
-                        "Sandbox".to_string(),
-                        Vec3::new(98785.898, 13339.8, -10386.2), // Vec3::new(15067000000.0, 0.0, 0.0),
-                        Vec3::new(0.0, 0.0, 0.0),
-                    );
-                    planets.write().await.insert(sandbox.uuid.clone(), sandbox.clone());
-                }
+
+                // the first player ever creates the server initial planets, the players
+                // coming after everyone left find them already there
+                let init_server = create_planets(&planets).await;
 
                 // create player and store it
                 
                 // store in variable z the number of players and multiply it by 10.0
-                let z = players.read().await.len() as f64 * 10.0;
+                let _z = players.read().await.len() as f64 * 10.0;
 
                 let player = props::player::Player::new(
                     event.username.clone(),
@@ -215,10 +328,11 @@ impl SimplePlugin for DyingstarPropsPlugin {
                 players.write().await.insert(PlayerId::from_str(&player.uuid).unwrap(), player.clone());
                 new_players.push(player.clone());
 
-                if first_player {
+                if init_server {
                     let payload = serde_json::json!({
                         "planets": planets.read().await.values().cloned().collect::<Vec<Testplanet>>(),
                         "player": player.clone(),
+                        "request_id": player.uuid.clone(),
                     });
 
                     if let Err(e) = events.emit_plugin("gameserverplugin", "init_server", &payload)
@@ -229,6 +343,7 @@ impl SimplePlugin for DyingstarPropsPlugin {
                 } else {
                     let payload = serde_json::json!({
                         "player": player.clone(),
+                        "request_id": player.uuid.clone(),
                     });
 
                     if let Err(e) = events.emit_plugin("gameserverplugin", "add_props", &payload)
@@ -240,7 +355,7 @@ impl SimplePlugin for DyingstarPropsPlugin {
                 
 
                 // send all props to the new client
-                let props = serde_json::json!({
+                let _props = serde_json::json!({
                     "type": "player_props",
                     "planets": planets.read().await.values().cloned().collect::<Vec<Testplanet>>(),
                     "players": players.read().await.values().cloned().collect::<Vec<Player>>(),
@@ -278,6 +393,7 @@ impl SimplePlugin for DyingstarPropsPlugin {
 
         // create fresh clones for the spawn_request handler (avoid moving same Arc into multiple closures)
         let boxes50cm_for_spawn = self.boxes50cm.clone();
+        let spawn_requests_for_spawn = self.spawn_requests.clone();
         let events_for_spawn = events.clone();
         let owned_runtime_for_spawn = owned_runtime.clone();
 
@@ -290,35 +406,16 @@ impl SimplePlugin for DyingstarPropsPlugin {
             // clone the event and the boxes Arc for the spawned async task
             let event_task = event.clone();
             let boxes_for_task = boxes50cm_for_spawn.clone();
+            let spawn_requests = spawn_requests_for_spawn.clone();
 
             rt.spawn(async move {
                 // check if event["type"] == "box50cm" or "box4m" or "ship" with match
                 match event_task["data"]["type"].as_str().unwrap_or("") {
                     "box50cm" => {
                         println!("SPAWN BOX50CM YEAH");
-                        // spawn box50cm
-                        // create Box50cm and store it
-                        let box50cm = Box50cm::new(
-                            Vec3::new(0.0, 0.0, 0.0),
-                            Vec3::new(0.0, 0.0, 0.0),
-                            "".to_string(),
-                        );
-                        let box50cm_id = uuid::Uuid::new_v4().to_string();
-
-                        // store box in boxes50cm (use the cloned Arc inside async task)
-                        {
-                            let mut boxes = boxes_for_task.write().await;
-                            boxes.insert(box50cm_id.clone(), box50cm.clone());
-                        }
-
-                        let payload = serde_json::json!({
-                            "box50cm": box50cm.clone(),
-                            "player_uuid": event_task["data"]["player_uuid"].as_str().unwrap_or(""),
-                        });
-
-                        if let Err(e) = events.emit_plugin("gameserverplugin", "add_prop", &payload).await {
-                            tracing::error!("Failed to emit plugin event to propsplugin, add_prop: {}", e);
-                        }
+                        let requester = serde_json::from_value::<PlayerId>(event_task["player_id"].clone()).ok();
+                        let player_uuid = event_task["data"]["player_uuid"].as_str().unwrap_or("");
+                        spawn_box50cm(&boxes_for_task, &spawn_requests, &events, requester, player_uuid).await;
                     },
                     "box4m" => {
                         // spawn box4m
@@ -338,43 +435,71 @@ impl SimplePlugin for DyingstarPropsPlugin {
         }).await.unwrap();
 
 
+        // actions of the players that create props, already checked by ds_game_server
+        let boxes50cm_for_action = self.boxes50cm.clone();
+        let spawn_requests_for_action = self.spawn_requests.clone();
+        let events_for_action = events.clone();
+        let owned_runtime_for_action = owned_runtime.clone();
+        let rt_handle_for_action = rt_handle.clone();
+        events.on_plugin("propsplugin", "player_action", move |event: PlayerActionData| {
+            let boxes50cm = boxes50cm_for_action.clone();
+            let spawn_requests = spawn_requests_for_action.clone();
+            let events = events_for_action.clone();
+            let rt = rt_handle_for_action.clone();
+            let _owned_rt = owned_runtime_for_action.clone();
+            rt.spawn(async move {
+                match event.action.as_str() {
+                    "spawn_box50cm" => {
+                        spawn_box50cm(&boxes50cm, &spawn_requests, &events, Some(event.player_id), &event.player_uuid).await;
+                    }
+                    _ => error!("Unknown player action for props: {}", event.action),
+                }
+            });
+            Ok(())
+        }).await.unwrap();
+
+        let players_clone2 = self.players.clone();
         let events_clone2 = events.clone();
         let owned_runtime_clone2 = owned_runtime.clone();
         // use the separate clone for the second handler
         let rt_handle2 = rt_handle_for_position_update.clone();
+        let regions_up2 = self.regions_up.clone();
+        let ticks2 = self.ticks.clone();
         events.on_plugin("propsplugin", "players_position_update", move |event: serde_json::Value| {
- 
-            // TODO update position and rotation of the player
-            // println!("PROP Receive player position update: {:?}", event);
-            // search in players the player has uuid of event["player"]["player_id"]
-            // let player_id = event["player_id"].as_str().unwrap_or("");
-            // let new_position = event["player"]["pos"].as_array().unwrap_or(&vec![]);
-            // let new_rotation = event["player"]["rot"].as_array().unwrap_or(&vec![]);
-            // if player_id != "" && new_position.len() == 3 && new_rotation.len() == 3 {
-            //     let mut players = players_clone.write();
-            //     if let Some(player) = players.get_mut(player_id) {
-            //         player.position = Vec3::new(
-            //             new_position[0],
-            //             new_position[1],
-            //             new_position[2],
-            //         );
-            //         player.rotation = Vec3::new(
-            //             new_rotation[0],
-            //             new_rotation[1],
-            //             new_rotation[2],
-            //         );
-            //         // println!("Updated player position: {:?}", player);
-            //     }
-            // }
-
+            if !game_server_up(&regions_up2) {
+                return Ok(());
+            }
+            // lets the clients order the updates and interpolate between them
+            let tick = ticks2.load(Ordering::Relaxed);
+            let server_time_ms = server_time_ms();
 
             // broadcast new position to all clients
+            let players = players_clone2.clone();
             let events = events_clone2.clone();
             let rt = rt_handle2.clone();
             let _owned_rt = owned_runtime_clone2.clone();
             rt.spawn(async move {
+                // keep the latest position and rotation, a world snapshot sends them back to the game server
+                if let Some(updates) = event["players"].as_array() {
+                    let mut players = players.write().await;
+                    for update in updates {
+                        let player_id = update["uuid"].as_str().and_then(|uuid| PlayerId::from_str(uuid).ok());
+                        let Some(player) = player_id.and_then(|player_id| players.get_mut(&player_id)) else {
+                            continue;
+                        };
+                        if let Ok(position) = serde_json::from_value(update["position"].clone()) {
+                            player.position = position;
+                        }
+                        if let Ok(rotation) = serde_json::from_value(update["rotation"].clone()) {
+                            player.rotation = rotation;
+                        }
+                    }
+                }
+
                 let announcement = serde_json::json!({
                     "type": "update_props",
+                    "tick": tick,
+                    "server_time_ms": server_time_ms,
                     "planets": serde_json::json!([]),
                     "players": event["players"],
                 });
@@ -400,27 +525,21 @@ impl SimplePlugin for DyingstarPropsPlugin {
             let rt = rt_handle_for_disconnect.clone();
             let _owned_rt = owned_runtime_for_disconnect.clone();
 
-            let internal_uuid = event.player_id.clone();
+            let internal_uuid = event.player_id;
 
             // spawn async task to use .await inside
             rt.spawn(async move {
-                // println!("PROP Player disconnected event: {:?}", event);
-                // println!("PROP Player disconnected, list of players {:?}", players.read().await);
-                // acquire write lock to remove the player
-                let mut players_map = players.write().await;
-                // loop on players_map for player have the internal_uuid = internal_uuid
-                for (uuid, player) in players_map.iter() {
-                    if player.internal_uuid == internal_uuid.to_string() {
-                        println!("Found player: {:?}", player);
-                        // send to all clients the player disconnected
-                        let payload = serde_json::json!({
-                            "type": "delete_player",
-                            "player_uuid": player.uuid.clone(),
-                        });
-                        println!("Broadcasting player disconnected: {:?}", payload);
-                        if let Err(e) = events.broadcast(&payload).await {
-                            error!("Failed to broadcast event: {}", e);
-                        }
+                // remove the players of this connection, so they are not in the next world snapshot
+                for player_uuid in remove_players(&players, &internal_uuid.to_string()).await {
+                    info!("🔧 DyingstarPropsPlugin: Player {} disconnected, removing {}", internal_uuid, player_uuid);
+                    // send to all clients the player disconnected
+                    let payload = serde_json::json!({
+                        "type": "delete_player",
+                        "player_uuid": player_uuid,
+                    });
+                    debug!("Broadcasting player disconnected: {:?}", payload);
+                    if let Err(e) = events.broadcast(&payload).await {
+                        error!("Failed to broadcast event: {}", e);
                     }
                 }
             });
@@ -433,13 +552,22 @@ impl SimplePlugin for DyingstarPropsPlugin {
         let owned_runtime_clone3 = owned_runtime.clone();
         // use the separate clone for the second handler
         let rt_handle3 = rt_handle_for_position_update.clone();
+        let regions_up3 = self.regions_up.clone();
+        let ticks3 = self.ticks.clone();
         events.on_plugin("propsplugin", "props_position_update", move |event: serde_json::Value| {
+            if !game_server_up(&regions_up3) {
+                return Ok(());
+            }
+            let tick = ticks3.load(Ordering::Relaxed);
+            let server_time_ms = server_time_ms();
             let events = events_clone3.clone();
             let rt = rt_handle3.clone();
             let _owned_rt = owned_runtime_clone3.clone();
             rt.spawn(async move {
                 let announcement = serde_json::json!({
                     "type": "props_position_update",
+                    "tick": tick,
+                    "server_time_ms": server_time_ms,
                     "props": event["props"],
                 });
 
@@ -451,6 +579,153 @@ impl SimplePlugin for DyingstarPropsPlugin {
             Ok(())
         }).await.unwrap();
 
+        let ticks = self.ticks.clone();
+        events.on_core("server_tick", move |_event: serde_json::Value| {
+            ticks.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }).await.map_err(|e| PluginError::ExecutionError(e.to_string()))?;
+
+        // NTP-style clock sync: the client gets the server clock when its request was
+        // received (t1) and answered (t2), and computes its offset from its own t0 and t3
+        let ticks_for_sync = self.ticks.clone();
+        let rt_handle_for_sync = rt_handle_for_position_update.clone();
+        let owned_runtime_for_sync = owned_runtime.clone();
+        events.on_client_with_connection("time", "sync", move |wrapper: ClientEventWrapper<TimeSyncRequest>, connection| {
+            let t1 = server_time_ms();
+            let tick = ticks_for_sync.load(Ordering::Relaxed);
+            let _owned_rt = owned_runtime_for_sync.clone();
+            rt_handle_for_sync.spawn(async move {
+                let answer = serde_json::json!({
+                    "type": "time_sync",
+                    "t0": wrapper.data.t0,
+                    "t1": t1,
+                    "t2": server_time_ms(),
+                    "tick": tick,
+                });
+                if let Err(e) = connection.respond_json(&answer).await {
+                    error!("Failed to answer time sync of player {}: {}", connection.player_id, e);
+                }
+            });
+            Ok(())
+        }).await.map_err(|e| PluginError::ExecutionError(e.to_string()))?;
+
+        // stop broadcasting positions while every game server is unreachable, clients keep the last ones
+        let regions_up = self.regions_up.clone();
+        events.on_plugin("gameserverplugin", "link_down", move |event: serde_json::Value| {
+            let region = event["region"].as_str().unwrap_or("default").to_string();
+            info!("🔧 DyingstarPropsPlugin: Game server link of region {} down ({})", region, event["reason"]);
+            regions_up.lock().unwrap().insert(region, false);
+            if !game_server_up(&regions_up) {
+                info!("🔧 DyingstarPropsPlugin: No game server reachable, pausing position broadcasts");
+            }
+            Ok(())
+        }).await.unwrap();
+
+        let regions_up = self.regions_up.clone();
+        events.on_plugin("gameserverplugin", "link_up", move |event: serde_json::Value| {
+            let region = event["region"].as_str().unwrap_or("default").to_string();
+            info!("🔧 DyingstarPropsPlugin: Game server link of region {} up, broadcasting its positions", region);
+            regions_up.lock().unwrap().insert(region, true);
+            Ok(())
+        }).await.unwrap();
+
+        // the game server answered (or not) an add_props/add_prop: roll back what it failed to spawn
+        let players_for_result = self.players.clone();
+        let boxes50cm_for_result = self.boxes50cm.clone();
+        let spawn_requests_for_result = self.spawn_requests.clone();
+        let events_for_result = events.clone();
+        let owned_runtime_for_result = owned_runtime.clone();
+        let rt_handle_for_result = rt_handle.clone();
+        events.on_plugin("propsplugin", "spawn_result", move |event: SpawnResult| {
+            let players = players_for_result.clone();
+            let boxes50cm = boxes50cm_for_result.clone();
+            let spawn_requests = spawn_requests_for_result.clone();
+            let events = events_for_result.clone();
+            let context = context.clone();
+            let rt = rt_handle_for_result.clone();
+            let _owned_rt = owned_runtime_for_result.clone();
+            rt.spawn(async move {
+                let requester = spawn_requests.write().await.remove(&event.request_id);
+                if event.ok {
+                    return;
+                }
+                if event.link_lost {
+                    info!("🔧 DyingstarPropsPlugin: Game server link lost before answering {} {}, resynced on reconnect", event.command, event.request_id);
+                    return;
+                }
+                let reason = event.reason.clone().unwrap_or_default();
+                error!("🔧 DyingstarPropsPlugin: Game server failed {} {}: {}", event.command, event.request_id, reason);
+
+                let (requester, prop) = match event.command.as_str() {
+                    "add_prop" => {
+                        boxes50cm.write().await.remove(&event.request_id);
+                        (requester, "box50cm")
+                    }
+                    "add_props" => {
+                        let Ok(player_id) = PlayerId::from_str(&event.request_id) else {
+                            return;
+                        };
+                        let Some(player) = players.write().await.remove(&player_id) else {
+                            return;
+                        };
+                        // the other clients already received this player
+                        let payload = serde_json::json!({
+                            "type": "delete_player",
+                            "player_uuid": player.uuid.clone(),
+                        });
+                        if let Err(e) = events.broadcast(&payload).await {
+                            error!("Failed to broadcast event: {}", e);
+                        }
+                        (PlayerId::from_str(&player.internal_uuid).ok(), "player")
+                    }
+                    _ => (requester, ""),
+                };
+
+                if let Some(requester) = requester {
+                    let payload = serde_json::json!({
+                        "type": "spawn_failed",
+                        "prop": prop,
+                        "prop_id": event.request_id,
+                        "reason": reason,
+                    });
+                    if let Err(e) = context.send_to_player(requester, payload.to_string().as_bytes()).await {
+                        error!("Failed to send spawn failure to player {}: {}", requester, e);
+                    }
+                }
+            });
+
+            Ok(())
+        }).await.unwrap();
+
+        // the game server link came back, send it everything we know so its scene matches ours
+        let players_for_snapshot = self.players.clone();
+        let planets_for_snapshot = self.planets.clone();
+        let boxes50cm_for_snapshot = self.boxes50cm.clone();
+        let events_for_snapshot = events.clone();
+        let owned_runtime_for_snapshot = owned_runtime.clone();
+        let rt_handle_for_snapshot = rt_handle.clone();
+        events.on_plugin("propsplugin", "world_snapshot_request", move |_event: serde_json::Value| {
+            let players = players_for_snapshot.clone();
+            let planets = planets_for_snapshot.clone();
+            let boxes50cm = boxes50cm_for_snapshot.clone();
+            let events = events_for_snapshot.clone();
+            let rt = rt_handle_for_snapshot.clone();
+            let _owned_rt = owned_runtime_for_snapshot.clone();
+            rt.spawn(async move {
+                let snapshot = serde_json::json!({
+                    "planets": planets.read().await.values().cloned().collect::<Vec<Testplanet>>(),
+                    "players": players.read().await.values().cloned().collect::<Vec<Player>>(),
+                    "boxes50cm": boxes50cm.read().await.values().cloned().collect::<Vec<Box50cm>>(),
+                });
+
+                if let Err(e) = events.emit_plugin("gameserverplugin", "world_snapshot", &snapshot).await {
+                    error!("Failed to emit plugin event to gameserverplugin: {}", e);
+                }
+            });
+
+            Ok(())
+        }).await.unwrap();
+
         info!("🔧 DyingstarPropsPlugin: ✅ All handlers registered successfully!");
         Ok(())
     }
@@ -482,3 +757,32 @@ impl SimplePlugin for DyingstarPropsPlugin {
 
 // Create the plugin using the macro
 create_simple_plugin!(DyingstarPropsPlugin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn planets_are_created_once_for_players_coming_back() {
+        let planets = RwLock::new(HashMap::new());
+        let players = RwLock::new(HashMap::new());
+        let player_uuid = PlayerId::new();
+        let internal_uuid = PlayerId::new().to_string();
+        let alice = Player::new(
+            "alice".to_string(),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            internal_uuid.clone(),
+            player_uuid.to_string(),
+        );
+
+        assert!(create_planets(&planets).await);
+        players.write().await.insert(player_uuid, alice);
+        assert_eq!(remove_players(&players, &internal_uuid).await, vec![player_uuid.to_string()]);
+        assert!(players.read().await.is_empty());
+
+        // everyone left, the next login finds the planets already there
+        assert!(!create_planets(&planets).await);
+        assert_eq!(planets.read().await.len(), 1);
+    }
+}
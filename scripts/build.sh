@@ -7,6 +7,10 @@ build() {
     cd "$1"
     RUSTFLAGS="" cargo build --release
     cp target/release/*.so ../Horizon/plugins
+    # install the default settings file, never overwrite a local one
+    if [ -f "$1.toml" ]; then
+        cp -n "$1.toml" ../Horizon/plugins/
+    fi
     cd ..
 }
 